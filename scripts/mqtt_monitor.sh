@@ -0,0 +1,8 @@
+#!/bin/bash
+
+# Shows everything the thermal-camera publishes over MQTT, broker host as argument (default localhost)
+# Commands can be sent with e.g.:
+#   mosquitto_pub -t thermal-camera/command/palette -m Gray
+#   mosquitto_pub -t thermal-camera/command/capture -m ""
+
+mosquitto_sub -h "${1:-localhost}" -v -t 'thermal-camera/#' -t 'homeassistant/+/thermal_camera_+/#'
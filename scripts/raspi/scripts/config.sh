@@ -27,3 +27,6 @@ sudo chmod a+x /opt/thermal-camera/run_thermal_camera.sh
 # Configure systemctl service
 sudo cp ./files/thermal-camera.service /etc/systemd/system/thermal-camera.service
 sudo systemctl enable thermal-camera
+
+# Installed, but not enabled; for unattended monitoring disable the GUI service and enable this one
+sudo cp ./files/thermal-camera-timelapse.service /etc/systemd/system/thermal-camera-timelapse.service
@@ -4,4 +4,4 @@
 
 ./scripts/upload_bin.sh
 
-ssh -Y thermal-camera@thermal-camera '/opt/thermal-camera/bin/thermal-camera -w'
+ssh -Y thermal-camera@thermal-camera '/opt/thermal-camera/bin/thermal-camera gui'
@@ -1,60 +1,189 @@
-use structopt::StructOpt;
-
 mod bsp;
+mod cli;
+mod client;
+mod convert;
+mod live;
 mod mlx;
+mod mqtt;
+mod server;
+mod stream;
+mod timelapse;
 mod window;
 
 use mlx::ColorTypes;
-use mlx::ImageRead;
+use mlx::{RangeModes, SaturationColors, GainModes, IsothermModes, IsothermColors, Interpolations, TemperatureUnits};
+use bsp::bsp_export::ExportFormats;
+use bsp::bsp_record::RecordFormats;
 
 pub fn run() {
-    let opt = Opt::from_args();
+    cli::run();
+}
 
-    if opt.windowed {
-        window::open_window();
-    }
-    else {
-        let path = opt.filename.as_str();
-        let width = mlx::PIXELS_WIDTH as u32;
-        let height = mlx::PIXELS_HEIGHT as u32;
+/*
+ * All settings, merged from the config file, environment and command line
+ * by cli::run and saved by the GUI
+ */
+#[derive(Debug, Clone)]
+pub struct Opt {
+    color_type: ColorTypes,
 
-        let output = get_mlx_output(&opt);
+    // Set through the subcommands, the sensor keeps its own value
+    framerate: mlx::Framerates,
 
-        bsp::write_png(path, &output.pixels, width, height);
-    }
-}
+    // Applied by the GUI on start, like the framerate
+    resolution: mlx::Resolutions,
 
-fn get_mlx_output(args: &Opt) -> ImageRead {
-    return mlx::take_image(&args.color_type).unwrap();
-}
+    // 0.1 to 1.0, e.g. 0.95 for most painted or organic surfaces
+    emissivity: f32,
 
-#[derive(Debug, StructOpt, Clone)]
-#[structopt(name = "MLX driver")]
-pub struct Opt {
-    #[structopt(default_value = "out.png")]
-    filename: String,
+    range_mode: RangeModes,
+    range_min: f32,
+    range_max: f32,
+    saturation: SaturationColors,
+    gain_mode: GainModes,
+    percentile_low: f32,
+    percentile_high: f32,
+    isotherm_mode: IsothermModes,
+    isotherm_min: f32,
+    isotherm_max: f32,
+    isotherm_color: IsothermColors,
+    isotherm_gray: bool,
+    interpolation: Interpolations,
+    scale_factor: usize,
 
-    #[structopt(default_value = "hue")]
-    color_type: ColorTypes,
+    save_format: ExportFormats,
+    record_format: RecordFormats,
 
-    #[structopt(default_value = "2")]
-    framerate: mlx::Framerates,
+    // Maximum length of a recording in seconds
+    record_duration: u32,
+
+    // Directory captures are saved to, detects removable storage if not set
+    save_dir: Option<String>,
+
+    // Name of saved captures, tokens: {date} {time} {seq} {job} {session} {max}
+    filename_template: String,
+
+    // Job or site name, used by the {job} token
+    job: String,
 
-    #[structopt(short, long)]
-    windowed: bool,
+    // Captures of a session go into a folder of the same name
+    session: Option<String>,
 
-    #[structopt(short, long)]
+    // Unit of command line captures and conversions, the GUI always uses Celsius
+    unit: TemperatureUnits,
+
+    // Serves live view and temperatures over HTTP while the GUI runs
+    http_enabled: bool,
+    http_port: u16,
+
+    // Needed by every HTTP request, the server doesn't start without
+    http_token: String,
+
+    // Raw temperatures over TCP for other programs, see server_tcp
+    tcp_enabled: bool,
+    tcp_port: u16,
+
+    // Publishes temperatures to an MQTT broker while the GUI runs
+    mqtt_enabled: bool,
+    mqtt_host: String,
+    mqtt_port: u16,
+
+    // Anonymous if empty
+    mqtt_username: String,
+    mqtt_password: String,
+
+    // Base of all topics, e.g. "thermal-camera/state"
+    mqtt_topic: String,
+
+    // Seconds between two state messages
+    mqtt_interval: u32,
+
+    // Home Assistant discovery, disabled if empty
+    mqtt_discovery_prefix: String,
+
+    // Alarm once the min temperature is below or the max temperature above, in the unit
+    // of the published temperatures (Celsius with the GUI), off if not set
+    mqtt_alarm_min: Option<f32>,
+    mqtt_alarm_max: Option<f32>,
+
+    // Only used by the GUI
     left_handed: bool,
+
+    // Name of the profile last switched to
+    profile: Option<String>,
 }
 
 impl Default for Opt {
     fn default() -> Self {
         Self {
-            filename: "out.png".to_string(),
             color_type: ColorTypes::Hue,
             framerate: mlx::Framerates::Two,
-            windowed: false,
+            resolution: mlx::Resolutions::Eighteen,
+            emissivity: mlx::DEFAULT_EMISSIVITY,
+            range_mode: RangeModes::Auto,
+            range_min: 20.0,
+            range_max: 40.0,
+            saturation: SaturationColors::Off,
+            gain_mode: GainModes::Linear,
+            percentile_low: 1.0,
+            percentile_high: 99.0,
+            isotherm_mode: IsothermModes::Off,
+            isotherm_min: 30.0,
+            isotherm_max: 40.0,
+            isotherm_color: IsothermColors::Green,
+            isotherm_gray: false,
+            interpolation: Interpolations::Nearest,
+            scale_factor: 1,
+            save_format: ExportFormats::Png,
+            record_format: RecordFormats::Gif,
+            record_duration: 30,
+            save_dir: None,
+            filename_template: bsp::bsp_naming::DEFAULT_TEMPLATE.to_string(),
+            job: "".to_string(),
+            session: None,
+            unit: TemperatureUnits::Celsius,
+            http_enabled: false,
+            http_port: server::DEFAULT_PORT,
+            http_token: "".to_string(),
+            tcp_enabled: false,
+            tcp_port: server::server_tcp::DEFAULT_PORT,
+            mqtt_enabled: false,
+            mqtt_host: "localhost".to_string(),
+            mqtt_port: mqtt::DEFAULT_PORT,
+            mqtt_username: "".to_string(),
+            mqtt_password: "".to_string(),
+            mqtt_topic: mqtt::DEFAULT_TOPIC.to_string(),
+            mqtt_interval: 10,
+            mqtt_discovery_prefix: mqtt::DEFAULT_DISCOVERY_PREFIX.to_string(),
+            mqtt_alarm_min: None,
+            mqtt_alarm_max: None,
             left_handed: false,
+            profile: None,
         }
     }
 }
+
+impl Opt {
+    pub fn color_settings(&self) -> mlx::ColorSettings {
+        let range = match self.range_mode {
+            RangeModes::Auto => None,
+            RangeModes::Locked | RangeModes::Manual => Some((self.range_min, self.range_max)),
+        };
+
+        return mlx::ColorSettings {
+            color_type: self.color_type,
+            range,
+            saturation: self.saturation,
+            gain: self.gain_mode,
+            percentile: (self.percentile_low, self.percentile_high),
+            isotherm: mlx::Isotherm {
+                mode: self.isotherm_mode,
+                range: (self.isotherm_min, self.isotherm_max),
+                color: self.isotherm_color,
+                gray_background: self.isotherm_gray,
+            },
+            interpolation: self.interpolation,
+            scale_factor: self.scale_factor,
+        };
+    }
+}
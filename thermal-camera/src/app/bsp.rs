@@ -1,125 +1,325 @@
-use image::{RgbImage, Rgb};
-use std::{fs, io::{self, Write, Read}, str::FromStr};
-use chrono;
+use std::{fs, io, path::Path};
 use super::{Opt, mlx};
 
-const OPTIONS_PATH: &str = "/home/thermal-camera/thermal-camera-options.txt";
+pub mod bsp_config;
+pub mod bsp_export;
+pub mod bsp_naming;
+pub mod bsp_record;
+pub mod bsp_report;
+pub mod bsp_storage;
 
-pub fn check_usb() -> bool {
-    let mut paths = fs::read_dir("/dev").unwrap();
-    return paths.any(|val| {
-        return val.as_ref().unwrap().file_name() == "sda1";
-    });
-}
+use bsp_export::FrameMetadata;
+use bsp_storage::Storage;
+
+const PNG_KEY_METADATA: &str = "thermal-camera:metadata";
+const PNG_KEY_TEMPERATURES: &str = "thermal-camera:temperatures";
 
-pub fn get_usb_path() -> String {
-    return format!("/media/usb0/thermal-camera/{}.png", get_time());
+/*
+ * File path for a new capture inside the storage directory,
+ * named after the filename template and session of the options.
+ * The extensions are those of every file the capture is saved as.
+ */
+pub fn get_save_path(storage: &Storage, opt: &Opt, max_temp: Option<f32>, extensions: &[&str]) -> String {
+    let naming = bsp_naming::Naming {
+        template: &opt.filename_template,
+        job: &opt.job,
+        session: opt.session.as_deref(),
+        max_temp,
+    };
+
+    return bsp_naming::get_file_path(&storage.path, &naming, extensions);
 }
 
-fn get_time() -> String {
-    let t = chrono::offset::Local::now();
-    let date = t.date_naive().format("%Y-%m-%d");
-    let time = t.time().format("%H-%M-%S");
-    
-    let res = format!("{}_{}", date, time);
-    return res;
+// Files a capture can consist of, the first one found stands for the capture
+const CAPTURE_EXTENSIONS: [&str; 5] = ["png", "tiff", "tif", "npy", "csv"];
+
+/*
+ * One path per saved capture inside the directory and its session folders,
+ * newest first. Sidecars of a capture (e.g. the CSV and NumPy next to a PNG) aren't listed.
+ */
+pub fn list_captures(dir: &str) -> Vec<String> {
+    let mut res: Vec<(std::time::SystemTime, String)> = Vec::new();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut files = Vec::new();
+    let mut session_dirs = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            session_dirs.push(path);
+        }
+        else {
+            files.push(path);
+        }
+    }
+
+    push_captures(&mut res, files);
+
+    for session_dir in session_dirs {
+        let files = fs::read_dir(session_dir).into_iter().flatten().flatten().map(|e| e.path()).collect();
+        push_captures(&mut res, files);
+    }
+
+    res.sort_by(|a, b| b.cmp(a));
+
+    return res.into_iter().map(|(_, path)| path).collect();
 }
 
-pub fn write_png(file_path: &str, image: &[u8], width: u32, height: u32) {
-    let mut img_png = RgbImage::new(width, height);
+/*
+ * files are all inside one directory
+ */
+fn push_captures(captures: &mut Vec<(std::time::SystemTime, String)>, files: Vec<std::path::PathBuf>) {
+    let mut best: Vec<(String, usize, &std::path::PathBuf)> = Vec::new();
 
-    for y in 0..height {
-        for x in 0..width {
-            let index = (y * 3) * width + (x * 3);
+    for path in files.iter().filter(|p| p.is_file()) {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let rank = match CAPTURE_EXTENSIONS.iter().position(|e| *e == extension) {
+            Some(rank) => rank,
+            None => continue,
+        };
 
-            let r = image[index as usize + 0];
-            let g = image[index as usize + 1];
-            let b = image[index as usize + 2];
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
 
-            img_png.put_pixel(x, y, Rgb([r, g, b]));
+        match best.iter_mut().find(|(s, _, _)| *s == stem) {
+            Some(capture) => if rank < capture.1 {
+                *capture = (stem, rank, path);
+            },
+            None => best.push((stem, rank, path)),
         }
     }
 
-    fs::create_dir_all(get_path(&file_path.to_string())).unwrap();
-    img_png.save(file_path).unwrap();
+    for (_, _, path) in best {
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .unwrap_or(std::time::UNIX_EPOCH);
+
+        captures.push((modified, path.to_string_lossy().to_string()));
+    }
 }
 
-fn get_path(file_path: &String) -> String {
-    let mut parts = file_path.split_inclusive('/');
+/*
+ * Decodes a saved capture to RGB, downscaled to fit into max_size if given.
+ * PNGs are shown as saved, the other formats are colored with the given settings.
+ * Returns (pixels, width, height)
+ */
+pub fn read_capture_image(file_path: &str, max_size: Option<(u32, u32)>, settings: &mlx::ColorSettings) -> Result<(Vec<u8>, usize, usize), String> {
+    let is_png = Path::new(file_path).extension().map(|e| e.eq_ignore_ascii_case("png")).unwrap_or(false);
 
-    parts.next();
-    parts.next_back();
-    
-    let mut res = "".to_string();
+    let mut img = match is_png {
+        true => image::open(file_path).map_err(|err| err.to_string())?,
 
-    for p in parts {
-        res += p;
+        false => {
+            let (temperatures, _) = read_capture_temperatures(file_path)?;
+            let scaled = mlx::mlx_image::color_image(settings, &temperatures).scaled;
+
+            let rgb = image::RgbImage::from_raw(scaled.width as u32, scaled.height as u32, scaled.pixels)
+                .ok_or("Invalid image size".to_string())?;
+
+            image::DynamicImage::ImageRgb8(rgb)
+        },
+    };
+
+    if let Some((max_width, max_height)) = max_size {
+        img = img.thumbnail(max_width, max_height);
     }
 
-    return res;
+    let rgb = img.to_rgb8();
+    let (width, height) = rgb.dimensions();
+
+    return Ok((rgb.into_raw(), width as usize, height as usize));
 }
 
-pub fn write_options(opt: &Opt) -> io::Result<()> {
-    let opt_string = opt.parse_to_string();
-    let buf = opt_string.as_bytes();
+/*
+ * Temperatures in °C of a saved capture. PNGs carry them inside,
+ * other formats need the metadata file saved next to them.
+ * PNGs without embedded temperatures fall back to the CSV next to them.
+ */
+pub fn read_capture_temperatures(file_path: &str) -> Result<(mlx::TemperatureRead, FrameMetadata), String> {
+    let path = Path::new(file_path);
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
 
-    let mut f = fs::File::create(OPTIONS_PATH)?;
-    f.write_all(buf)?;
+    let png_read = match extension.as_deref() {
+        Some("png") => Some(read_png_temperatures(file_path)),
+        _ => None,
+    };
 
-    return Ok(());
+    let (mut temperatures, metadata) = match png_read {
+        Some(Ok(res)) => res,
+
+        _ => {
+            let metadata_json = fs::read_to_string(path.with_extension("json")).map_err(|err| err.to_string())?;
+            let metadata: FrameMetadata = serde_json::from_str(&metadata_json).map_err(|err| err.to_string())?;
+
+            let temperatures = match extension.as_deref() {
+                Some("npy") => bsp_export::read_npy(path).map_err(|err| err.to_string())?,
+                Some("tiff") | Some("tif") => bsp_export::read_tiff(path).map_err(|err| err.to_string())?,
+                _ => bsp_export::grid_from_string(
+                    &fs::read_to_string(path.with_extension("csv")).map_err(|err| err.to_string())?
+                )?,
+            };
+
+            (temperatures, metadata)
+        },
+    };
+
+    let unit = mlx::TemperatureUnits::from_symbol(&metadata.unit).unwrap_or(mlx::TemperatureUnits::Celsius);
+    temperatures.convert_unit(unit, mlx::TemperatureUnits::Celsius);
+
+    return Ok((temperatures, metadata));
 }
 
-pub fn read_options() -> Result<Opt, io::Error> {
-    let f_response = fs::File::open(OPTIONS_PATH);
-    if f_response.is_err() {
-        return Err(f_response.unwrap_err());
+/*
+ * One file per capture inside the directory that read_capture_temperatures
+ * can read. A PNG is preferred over the NumPy and CSV files next to it,
+ * and NumPy over a TIFF, which may have been scaled.
+ */
+pub fn list_radiometric_files(dir: &str) -> io::Result<Vec<String>> {
+    const PREFERENCE: [&str; 5] = ["png", "npy", "tiff", "tif", "csv"];
+
+    let mut captures: Vec<(String, usize, String)> = Vec::new();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_file() { continue; }
+
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let rank = match PREFERENCE.iter().position(|p| *p == extension) {
+            Some(rank) => rank,
+            None => continue,
+        };
+
+        let stem = get_capture_stem(&path.to_string_lossy());
+        let file_path = path.to_string_lossy().to_string();
+
+        match captures.iter_mut().find(|(s, _, _)| *s == stem) {
+            Some(capture) => if rank < capture.1 {
+                *capture = (stem, rank, file_path);
+            },
+            None => captures.push((stem, rank, file_path)),
+        }
     }
 
-    let mut f = f_response.unwrap();
-    
-    let mut buf = String::new();
-    f.read_to_string(&mut buf)?;
+    let mut res: Vec<String> = captures.into_iter().map(|(_, _, file_path)| file_path).collect();
+    res.sort();
+
+    return Ok(res);
+}
+
+/*
+ * File name without extension, "x.annotated.png" belongs to the same capture as "x.png"
+ */
+pub fn get_capture_stem(file_path: &str) -> String {
+    let stem = Path::new(file_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
 
-    return Ok(Opt::parse_from_string(buf));
+    return stem.strip_suffix(".annotated").unwrap_or(&stem).to_string();
 }
 
-impl Opt {
-    fn parse_to_string(&self) -> String {
-        format!(
-            "color:{}\nleft_hand:{}\n",
-            self.color_type.to_string(),
-            self.left_handed.to_string()
-        )
+/*
+ * Removes the picture together with the radiometric files saved next to it
+ */
+pub fn delete_capture(file_path: &str) -> io::Result<()> {
+    let path = Path::new(file_path);
+
+    for extension in ["csv", "npy", "json"] {
+        let sidecar = path.with_extension(extension);
+
+        if sidecar != path && sidecar.exists() {
+            fs::remove_file(sidecar)?;
+        }
     }
 
-    fn parse_from_string(s: String) -> Self {
-        let options = s.split('\n');
-        let mut res: Self = Self::default();
+    return fs::remove_file(path);
+}
+
+pub fn write_png(file_path: &str, image: &[u8], width: u32, height: u32, temperatures: &mlx::TemperatureRead, metadata: &FrameMetadata) -> io::Result<()> {
+    fs::create_dir_all(get_path(&file_path.to_string()))?;
 
-        for o in options {
-            let mut words = o.split(':');
-            let key = words.next();
-            let val = words.next();
+    let to_io_error = |err: png::EncodingError| io::Error::new(io::ErrorKind::Other, err.to_string());
 
-            if key.is_none() || val.is_none() {
-                continue;
-            }
+    let f = fs::File::create(file_path)?;
+    let mut encoder = png::Encoder::new(io::BufWriter::new(f), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
 
-            match key.unwrap() {
-                "color" => res.color_type = mlx::ColorTypes::from_str(
-                    val.unwrap()
-                ).unwrap_or(mlx::ColorTypes::Hue),
+    // Radiometric data, so the picture can be evaluated later
+    let metadata_json = serde_json::to_string(metadata)?;
+    let grid = bsp_export::grid_to_string(temperatures, None);
 
-                "left_hand" => res.left_handed = match val.unwrap() {
-                    "true" => true,
-                    "false" => false,
-                    _ => false,
-                },
+    encoder.add_text_chunk("Software".to_string(), "thermal-camera".to_string()).map_err(to_io_error)?;
+    encoder.add_itxt_chunk(PNG_KEY_METADATA.to_string(), metadata_json).map_err(to_io_error)?;
+    encoder.add_ztxt_chunk(PNG_KEY_TEMPERATURES.to_string(), grid).map_err(to_io_error)?;
 
-                _ => ()
-            }
-        }
+    let mut writer = encoder.write_header().map_err(to_io_error)?;
+    writer.write_image_data(&image[0..(width * height * 3) as usize]).map_err(to_io_error)?;
+    writer.finish().map_err(to_io_error)?;
+
+    return Ok(());
+}
+
+/*
+ * Recovers temperatures and metadata from a PNG written by write_png
+ */
+pub fn read_png_temperatures(file_path: &str) -> Result<(mlx::TemperatureRead, FrameMetadata), String> {
+    let f = fs::File::open(file_path).map_err(|err| err.to_string())?;
+    let decoder = png::Decoder::new(io::BufReader::new(f));
+    let reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let info = reader.info();
+
+    let metadata_json = info.utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == PNG_KEY_METADATA)
+        .ok_or("No thermal metadata found".to_string())?
+        .get_text()
+        .map_err(|err| err.to_string())?;
+
+    let grid = info.compressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == PNG_KEY_TEMPERATURES)
+        .ok_or("No temperatures found".to_string())?
+        .get_text()
+        .map_err(|err| err.to_string())?;
+
+    let metadata: FrameMetadata = serde_json::from_str(&metadata_json).map_err(|err| err.to_string())?;
+    let temperatures = bsp_export::grid_from_string(&grid)?;
+
+    return Ok((temperatures, metadata));
+}
+
+fn get_path(file_path: &String) -> String {
+    return Path::new(file_path)
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+}
+
+/*
+ * Returns (file name, content) of every file inside the palette directory
+ */
+pub fn read_palette_files(palette_dir: &Path) -> Vec<(String, String)> {
+    let mut res = Vec::new();
+
+    let paths = match fs::read_dir(palette_dir) {
+        Ok(paths) => paths,
+        Err(_) => return res,
+    };
 
-        return res;
+    for entry in paths.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        match fs::read_to_string(entry.path()) {
+            Ok(content) => res.push((file_name, content)),
+            Err(err) => println!("Failed to read palette {}: {}", file_name, err),
+        }
     }
+
+    return res;
 }
@@ -0,0 +1,910 @@
+use std::{env, fs, io::{self, Write}, os::unix::fs::{OpenOptionsExt, PermissionsExt}, path::{Path, PathBuf}, str::FromStr};
+use super::super::{Opt, mlx, mqtt};
+use super::{bsp_naming, bsp_record};
+
+pub const SCHEMA_VERSION: i64 = 1;
+
+const CONFIG_DIR: &str = "thermal-camera";
+const CONFIG_FILE: &str = "config.toml";
+
+// Overrides the config path, like --config
+const ENV_CONFIG_PATH: &str = "THERMAL_CAMERA_CONFIG";
+
+// Settings are overridden by e.g. THERMAL_CAMERA_RANGE_MIN for "range.min"
+const ENV_PREFIX: &str = "THERMAL_CAMERA_";
+
+// THERMAL_CAMERA_MQTT_PASSWORD_FILE reads the value from that file, e.g. for secrets
+const ENV_FILE_SUFFIX: &str = "_FILE";
+
+// The config holds the broker password and the token, so only the owner may read it
+const CONFIG_MODE: u32 = 0o600;
+
+// Profiles are kept next to the config file, one file each
+const PROFILE_DIR: &str = "profiles";
+const PROFILE_EXTENSION: &str = "toml";
+
+// Custom palettes are read from next to the config file as well
+const PALETTE_DIR: &str = "palettes";
+
+// Written before there was a config file, migrated once
+const LEGACY_OPTIONS_PATH: &str = "/home/thermal-camera/thermal-camera-options.txt";
+
+/*
+ * Every setting as "<section>.<key>", with the command line argument
+ * overriding it. Settings without argument are only set through the GUI,
+ * the config or the environment. Secrets have none, as arguments are visible to every user.
+ */
+pub const KEYS: [(&str, &str); 43] = [
+    ("sensor.framerate", ""),
+    ("sensor.resolution", ""),
+    ("sensor.emissivity", "emissivity"),
+    ("palette.color", "color-type"),
+    ("range.mode", "range-mode"),
+    ("range.min", "range-min"),
+    ("range.max", "range-max"),
+    ("range.saturation", "saturation"),
+    ("gain.mode", "gain-mode"),
+    ("gain.percentile_low", "percentile-low"),
+    ("gain.percentile_high", "percentile-high"),
+    ("isotherm.mode", "isotherm-mode"),
+    ("isotherm.min", "isotherm-min"),
+    ("isotherm.max", "isotherm-max"),
+    ("isotherm.color", "isotherm-color"),
+    ("isotherm.gray", "isotherm-gray"),
+    ("image.interpolation", "interpolation"),
+    ("image.scale_factor", "scale-factor"),
+    ("units.temperature", "unit"),
+    ("storage.save_dir", "save-dir"),
+    ("storage.save_format", "save-format"),
+    ("storage.filename_template", "filename-template"),
+    ("storage.job", "job"),
+    ("storage.session", "session"),
+    ("recording.format", ""),
+    ("recording.max_duration", ""),
+    ("network.http_enabled", "http-enabled"),
+    ("network.http_port", "http-port"),
+    ("network.http_token", ""),
+    ("network.tcp_enabled", "tcp-enabled"),
+    ("network.tcp_port", "tcp-port"),
+    ("mqtt.enabled", "mqtt-enabled"),
+    ("mqtt.host", "mqtt-host"),
+    ("mqtt.port", "mqtt-port"),
+    ("mqtt.username", "mqtt-username"),
+    ("mqtt.password", ""),
+    ("mqtt.topic", "mqtt-topic"),
+    ("mqtt.interval", "mqtt-interval"),
+    ("mqtt.discovery_prefix", "mqtt-discovery-prefix"),
+    ("mqtt.alarm_min", "mqtt-alarm-min"),
+    ("mqtt.alarm_max", "mqtt-alarm-max"),
+    ("ui.left_handed", ""),
+    ("ui.profile", ""),
+];
+
+// Belong to the device, so they stay when switching profiles
+const DEVICE_KEYS: [&str; 19] = [
+    "storage.save_dir",
+    "storage.session",
+    "network.http_enabled",
+    "network.http_port",
+    "network.http_token",
+    "network.tcp_enabled",
+    "network.tcp_port",
+    "mqtt.enabled",
+    "mqtt.host",
+    "mqtt.port",
+    "mqtt.username",
+    "mqtt.password",
+    "mqtt.topic",
+    "mqtt.interval",
+    "mqtt.discovery_prefix",
+    "mqtt.alarm_min",
+    "mqtt.alarm_max",
+    "ui.left_handed",
+    "ui.profile",
+];
+
+// Keys of the legacy "key:value" file and their replacement
+const LEGACY_KEYS: [(&str, &str); 23] = [
+    ("color", "palette.color"),
+    ("left_hand", "ui.left_handed"),
+    ("range_mode", "range.mode"),
+    ("range_min", "range.min"),
+    ("range_max", "range.max"),
+    ("saturation", "range.saturation"),
+    ("gain_mode", "gain.mode"),
+    ("percentile_low", "gain.percentile_low"),
+    ("percentile_high", "gain.percentile_high"),
+    ("isotherm_mode", "isotherm.mode"),
+    ("isotherm_min", "isotherm.min"),
+    ("isotherm_max", "isotherm.max"),
+    ("isotherm_color", "isotherm.color"),
+    ("isotherm_gray", "isotherm.gray"),
+    ("interpolation", "image.interpolation"),
+    ("scale_factor", "image.scale_factor"),
+    ("save_format", "storage.save_format"),
+    ("record_format", "recording.format"),
+    ("record_duration", "recording.max_duration"),
+    ("save_dir", "storage.save_dir"),
+    ("filename_template", "storage.filename_template"),
+    ("job", "storage.job"),
+    ("session", "storage.session"),
+];
+
+/*
+ * --config, then $THERMAL_CAMERA_CONFIG, then the XDG config directory
+ */
+pub fn get_config_path(cli_path: Option<&str>) -> PathBuf {
+    if let Some(path) = cli_path {
+        return PathBuf::from(path);
+    }
+
+    if let Ok(path) = env::var(ENV_CONFIG_PATH) {
+        return PathBuf::from(path);
+    }
+
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(PathBuf::from)
+        .or_else(|| env::var("HOME").ok().map(|home| Path::new(&home).join(".config")))
+        .unwrap_or_default();
+
+    return config_home.join(CONFIG_DIR).join(CONFIG_FILE);
+}
+
+/*
+ * Settings of the config file with environment overrides on top.
+ * Without config file the legacy options are migrated, or defaults used.
+ * Invalid entries are reported to stderr and skipped, so this never fails.
+ */
+pub fn load(config_path: &Path, profile: Option<&str>) -> Opt {
+    let mut opt = Opt::default();
+
+    match fs::read_to_string(config_path) {
+        Ok(content) => {
+            for err in apply_toml(&mut opt, &content, false) {
+                eprintln!("{}: {}", config_path.display(), err);
+            }
+        },
+
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            if let Some(legacy) = migrate_legacy(config_path) {
+                opt = legacy;
+            }
+        },
+
+        Err(err) => eprintln!("Failed to read {}: {}", config_path.display(), err),
+    }
+
+    if let Some(name) = profile {
+        apply_profile(config_path, name, &mut opt).unwrap_or_else(|err| eprintln!("{}", err));
+    }
+
+    for (key, _) in KEYS.iter() {
+        let var = format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_uppercase());
+        let file_var = format!("{}{}", var, ENV_FILE_SUFFIX);
+
+        if let Ok(val) = env::var(&var) {
+            opt.set_value(key, &val).unwrap_or_else(|err| eprintln!("{}: {}", var, err));
+        }
+        else if let Ok(path) = env::var(&file_var) {
+            match fs::read_to_string(&path) {
+                Ok(val) => opt.set_value(key, val.trim_end_matches(['\r', '\n'])).unwrap_or_else(|err| eprintln!("{}: {}", file_var, err)),
+                Err(err) => eprintln!("{}: Failed to read {}: {}", file_var, path, err),
+            }
+        }
+    }
+
+    return opt;
+}
+
+pub fn write_config(config_path: &Path, opt: &Opt) -> io::Result<()> {
+    return write_table(config_path, &opt.to_table(false));
+}
+
+/*
+ * Writes only the settings that differ between old and new into the config file.
+ * Values from the environment, the command line or --profile are in both,
+ * so they don't end up in the file unless changed.
+ */
+pub fn update_config(config_path: &Path, old: &Opt, new: &Opt) -> io::Result<()> {
+    let mut table = match fs::read_to_string(config_path) {
+        Ok(content) => toml::from_str::<toml::Table>(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?,
+
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Opt::default().to_table(false),
+        Err(err) => return Err(err),
+    };
+
+    let changed: Vec<&str> = KEYS
+        .iter()
+        .map(|(key, _)| *key)
+        .filter(|key| old.get_value(key) != new.get_value(key))
+        .collect();
+
+    if changed.is_empty() { return Ok(()); }
+
+    for key in changed {
+        let (section, name) = key.split_once('.').unwrap();
+
+        let values = table
+            .entry(section)
+            .or_insert(toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a section", section)))?;
+
+        match new.get_value(key) {
+            Some(value) => values.insert(name.to_string(), value),
+            None => values.remove(name),
+        };
+    }
+
+    return write_table(config_path, &table);
+}
+
+fn write_table(path: &Path, table: &toml::Table) -> io::Result<()> {
+    let content = toml::to_string(table)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(CONFIG_MODE)
+        .open(path)?;
+
+    // The mode is only used for new files, older ones were created readable for everyone
+    file.set_permissions(fs::Permissions::from_mode(CONFIG_MODE))?;
+
+    return file.write_all(content.as_bytes());
+}
+
+/*
+ * Names of all saved profiles, sorted
+ */
+pub fn list_profiles(config_path: &Path) -> Vec<String> {
+    return list_profile_files(&get_profile_dir(config_path))
+        .iter()
+        .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .collect();
+}
+
+/*
+ * Stores the settings, except those of the device, under the name.
+ * Returns the name as saved, it has to work as a file name.
+ */
+pub fn save_profile(config_path: &Path, name: &str, opt: &Opt) -> io::Result<String> {
+    let name = get_profile_name(name)?;
+    write_table(&get_profile_path(config_path, &name)?, &opt.to_table(true))?;
+
+    return Ok(name);
+}
+
+/*
+ * Overwrites the settings with those of the profile and marks it active
+ */
+pub fn apply_profile(config_path: &Path, name: &str, opt: &mut Opt) -> Result<(), String> {
+    let name = get_profile_name(name).map_err(|err| err.to_string())?;
+    let path = get_profile_path(config_path, &name).map_err(|err| err.to_string())?;
+
+    let content = fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read profile {}: {}", name, err))?;
+
+    for err in apply_toml(opt, &content, true) {
+        eprintln!("{}: {}", path.display(), err);
+    }
+
+    opt.profile = Some(name);
+
+    return Ok(());
+}
+
+pub fn rename_profile(config_path: &Path, name: &str, new_name: &str) -> io::Result<String> {
+    let new_name = get_profile_name(new_name)?;
+    let new_path = get_profile_path(config_path, &new_name)?;
+
+    if new_path.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Profile {} exists already", new_name)));
+    }
+
+    fs::rename(get_profile_path(config_path, name)?, new_path)?;
+
+    return Ok(new_name);
+}
+
+pub fn delete_profile(config_path: &Path, name: &str) -> io::Result<()> {
+    return fs::remove_file(get_profile_path(config_path, name)?);
+}
+
+/*
+ * Writes the profile into the directory, e.g. on a USB drive for other devices
+ */
+pub fn export_profile(config_path: &Path, name: &str, target_dir: &Path) -> io::Result<PathBuf> {
+    let source = get_profile_path(config_path, name)?;
+    let target = target_dir.join(source.file_name().unwrap_or_default());
+
+    fs::create_dir_all(target_dir)?;
+    fs::copy(source, &target)?;
+
+    return Ok(target);
+}
+
+/*
+ * Adds a profile file exported on another device, named after the file.
+ * Existing profiles of the same name are replaced.
+ */
+pub fn import_profile(config_path: &Path, file_path: &Path) -> io::Result<String> {
+    let content = fs::read_to_string(file_path)?;
+
+    if let Err(err) = toml::from_str::<toml::Table>(&content) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string()));
+    }
+
+    let stem = file_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name = get_profile_name(&stem)?;
+
+    let path = get_profile_path(config_path, &name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, content)?;
+
+    return Ok(name);
+}
+
+/*
+ * Profiles exported into the directory, to offer them for import
+ */
+pub fn list_profile_files(dir: &Path) -> Vec<PathBuf> {
+    let mut res: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map(|e| e == PROFILE_EXTENSION).unwrap_or(false))
+            .collect())
+        .unwrap_or_default();
+
+    res.sort();
+    return res;
+}
+
+fn get_profile_dir(config_path: &Path) -> PathBuf {
+    return config_path.parent().unwrap_or(Path::new("")).join(PROFILE_DIR);
+}
+
+pub fn get_palette_dir(config_path: &Path) -> PathBuf {
+    return config_path.parent().unwrap_or(Path::new("")).join(PALETTE_DIR);
+}
+
+/*
+ * Every name is sanitized first, so e.g. "../config" can't leave the profile directory
+ */
+fn get_profile_path(config_path: &Path, name: &str) -> io::Result<PathBuf> {
+    let name = get_profile_name(name)?;
+
+    return Ok(get_profile_dir(config_path).join(format!("{}.{}", name, PROFILE_EXTENSION)));
+}
+
+fn get_profile_name(name: &str) -> io::Result<String> {
+    let name = bsp_naming::sanitize(name);
+
+    if name.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Profile name is empty"));
+    }
+
+    return Ok(name);
+}
+
+/*
+ * Returns the problems found, the valid settings are applied regardless.
+ * For profiles the settings of the device are left alone.
+ */
+fn apply_toml(opt: &mut Opt, content: &str, profile: bool) -> Vec<String> {
+    let table: toml::Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(err) => return vec![err.to_string()],
+    };
+
+    return apply_table(opt, table, profile);
+}
+
+fn apply_table(opt: &mut Opt, table: toml::Table, profile: bool) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    let version = table.get("version").and_then(|v| v.as_integer()).unwrap_or(SCHEMA_VERSION);
+    let table = match migrate(table, version) {
+        Ok(table) => table,
+        Err(err) => {
+            errors.push(err);
+            return errors;
+        },
+    };
+
+    for (section, values) in table.iter() {
+        if section == "version" { continue; }
+
+        let values = match values.as_table() {
+            Some(values) => values,
+            None => {
+                errors.push(format!("Unknown setting {}", section));
+                continue;
+            },
+        };
+
+        for (key, value) in values.iter() {
+            let key = format!("{}.{}", section, key);
+
+            if profile && DEVICE_KEYS.contains(&key.as_str()) { continue; }
+
+            let res = match value {
+                toml::Value::String(s) => opt.set_value(&key, s),
+                toml::Value::Integer(i) => opt.set_value(&key, &i.to_string()),
+                toml::Value::Float(f) => opt.set_value(&key, &f.to_string()),
+                toml::Value::Boolean(b) => opt.set_value(&key, &b.to_string()),
+                _ => Err(format!("Unsupported value for {}", key)),
+            };
+
+            if let Err(err) = res {
+                errors.push(err);
+            }
+        }
+    }
+
+    return errors;
+}
+
+/*
+ * Brings the table of an older schema version up to date, one version at a time.
+ * Version 0 is the legacy text file, version 1 the first config file.
+ */
+fn migrate(mut table: toml::Table, version: i64) -> Result<toml::Table, String> {
+    if version < 0 {
+        return Err(format!("Invalid config version {}", version));
+    }
+
+    if version > SCHEMA_VERSION {
+        eprintln!("Config version {} is newer than {}, unknown settings are skipped", version, SCHEMA_VERSION);
+    }
+
+    if version < 1 {
+        table = migrate_v0(table);
+    }
+
+    return Ok(table);
+}
+
+/*
+ * Version 0 has the keys of the legacy file at the top level,
+ * they are moved into their sections. Unknown keys are dropped.
+ */
+fn migrate_v0(table: toml::Table) -> toml::Table {
+    let mut migrated = toml::Table::new();
+    migrated.insert("version".to_string(), toml::Value::Integer(1));
+
+    for (key, value) in table.into_iter() {
+        let new_key = match LEGACY_KEYS.iter().find(|(k, _)| *k == key) {
+            Some((_, new_key)) => new_key,
+            None => continue,
+        };
+
+        let (section, name) = new_key.split_once('.').unwrap();
+
+        migrated
+            .entry(section)
+            .or_insert(toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .unwrap()
+            .insert(name.to_string(), value);
+    }
+
+    return migrated;
+}
+
+/*
+ * Converts the legacy options into a config file, the old file is kept renamed
+ */
+fn migrate_legacy(config_path: &Path) -> Option<Opt> {
+    let content = fs::read_to_string(LEGACY_OPTIONS_PATH).ok()?;
+    let opt = parse_legacy(&content);
+
+    match write_config(config_path, &opt) {
+        Ok(()) => {
+            let backup = format!("{}.migrated", LEGACY_OPTIONS_PATH);
+            fs::rename(LEGACY_OPTIONS_PATH, &backup).unwrap_or_default();
+
+            eprintln!("Migrated {} to {}", LEGACY_OPTIONS_PATH, config_path.display());
+        },
+
+        Err(err) => eprintln!("Failed to write {}: {}", config_path.display(), err),
+    }
+
+    return Some(opt);
+}
+
+/*
+ * One "key:value" per line, read as a version 0 table and migrated from there
+ */
+fn parse_legacy(content: &str) -> Opt {
+    let mut table = toml::Table::new();
+    table.insert("version".to_string(), toml::Value::Integer(0));
+
+    for line in content.lines() {
+        let mut words = line.splitn(2, ':');
+
+        if let (Some(key), Some(val)) = (words.next(), words.next()) {
+            table.insert(key.to_string(), toml::Value::String(val.to_string()));
+        }
+    }
+
+    let mut opt = Opt::default();
+
+    for err in apply_table(&mut opt, table, false) {
+        eprintln!("{}: {}", LEGACY_OPTIONS_PATH, err);
+    }
+
+    return opt;
+}
+
+fn parse<T: FromStr>(key: &str, val: &str) -> Result<T, String> {
+    return val.trim().parse().map_err(|_| format!("Invalid value for {}: {}", key, val));
+}
+
+/*
+ * The token ends up in URLs and the viewer page, so it is kept to characters
+ * that need no escaping there. The value itself isn't shown, it's a secret.
+ */
+fn parse_token(key: &str, val: &str) -> Result<String, String> {
+    let val = val.trim();
+
+    if !val.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid value for {}: only letters, digits, '-' and '_' are allowed", key));
+    }
+
+    return Ok(val.to_string());
+}
+
+fn parse_optional(val: &str) -> Option<String> {
+    return match val.trim() {
+        "" => None,
+        val => Some(val.to_string()),
+    };
+}
+
+impl Opt {
+    /*
+     * Sets the value given as text, e.g. from an environment variable
+     */
+    pub fn set_value(&mut self, key: &str, val: &str) -> Result<(), String> {
+        match key {
+            "sensor.framerate" => self.framerate = parse(key, val)?,
+            "sensor.resolution" => self.resolution = parse(key, val)?,
+
+            "sensor.emissivity" => self.emissivity = parse::<f32>(key, val)?
+                .max(mlx::MIN_EMISSIVITY)
+                .min(1.0),
+
+            "palette.color" => self.color_type = parse(key, val)?,
+            "range.mode" => self.range_mode = parse(key, val)?,
+            "range.min" => self.range_min = parse(key, val)?,
+            "range.max" => self.range_max = parse(key, val)?,
+            "range.saturation" => self.saturation = parse(key, val)?,
+            "gain.mode" => self.gain_mode = parse(key, val)?,
+            "gain.percentile_low" => self.percentile_low = parse(key, val)?,
+            "gain.percentile_high" => self.percentile_high = parse(key, val)?,
+            "isotherm.mode" => self.isotherm_mode = parse(key, val)?,
+            "isotherm.min" => self.isotherm_min = parse(key, val)?,
+            "isotherm.max" => self.isotherm_max = parse(key, val)?,
+            "isotherm.color" => self.isotherm_color = parse(key, val)?,
+            "isotherm.gray" => self.isotherm_gray = parse(key, val)?,
+            "image.interpolation" => self.interpolation = parse(key, val)?,
+
+            "image.scale_factor" => self.scale_factor = parse::<usize>(key, val)?
+                .max(1)
+                .min(mlx::MAX_SCALE_FACTOR),
+
+            "units.temperature" => self.unit = parse(key, val)?,
+            "storage.save_dir" => self.save_dir = parse_optional(val),
+            "storage.save_format" => self.save_format = parse(key, val)?,
+
+            "storage.filename_template" => self.filename_template = parse_optional(val)
+                .unwrap_or(bsp_naming::DEFAULT_TEMPLATE.to_string()),
+
+            "storage.job" => self.job = val.trim().to_string(),
+            "storage.session" => self.session = parse_optional(val),
+            "recording.format" => self.record_format = parse(key, val)?,
+
+            "recording.max_duration" => self.record_duration = parse::<u32>(key, val)?
+                .max(1)
+                .min(bsp_record::MAX_DURATION),
+
+            "network.http_enabled" => self.http_enabled = parse(key, val)?,
+            "network.http_port" => self.http_port = parse(key, val)?,
+            "network.http_token" => self.http_token = parse_token(key, val)?,
+            "network.tcp_enabled" => self.tcp_enabled = parse(key, val)?,
+            "network.tcp_port" => self.tcp_port = parse(key, val)?,
+            "mqtt.enabled" => self.mqtt_enabled = parse(key, val)?,
+            "mqtt.host" => self.mqtt_host = val.trim().to_string(),
+            "mqtt.port" => self.mqtt_port = parse(key, val)?,
+            "mqtt.username" => self.mqtt_username = val.trim().to_string(),
+            "mqtt.password" => self.mqtt_password = val.to_string(),
+
+            "mqtt.topic" => self.mqtt_topic = parse_optional(val)
+                .map(|t| t.trim_end_matches('/').to_string())
+                .unwrap_or(mqtt::DEFAULT_TOPIC.to_string()),
+
+            "mqtt.interval" => self.mqtt_interval = parse::<u32>(key, val)?.max(1),
+            "mqtt.discovery_prefix" => self.mqtt_discovery_prefix = val.trim().to_string(),
+            "mqtt.alarm_min" => self.mqtt_alarm_min = parse_optional(val).map(|v| parse(key, &v)).transpose()?,
+            "mqtt.alarm_max" => self.mqtt_alarm_max = parse_optional(val).map(|v| parse(key, &v)).transpose()?,
+            "ui.left_handed" => self.left_handed = parse(key, val)?,
+            "ui.profile" => self.profile = parse_optional(val),
+
+            _ => return Err(format!("Unknown setting {}", key)),
+        }
+
+        return Ok(());
+    }
+
+    /*
+     * None for unset optional values, TOML has no null
+     */
+    pub fn get_value(&self, key: &str) -> Option<toml::Value> {
+        let string = |s: String| Some(toml::Value::String(s));
+        let float = |f: f32| Some(toml::Value::Float(f.to_string().parse().unwrap_or_default()));
+
+        return match key {
+            "sensor.framerate" => float(self.framerate.to_string().parse().unwrap_or_default()),
+            "sensor.resolution" => Some(toml::Value::Integer(self.resolution.to_string().parse().unwrap_or_default())),
+            "sensor.emissivity" => float(self.emissivity),
+            "palette.color" => string(self.color_type.to_string()),
+            "range.mode" => string(self.range_mode.to_string()),
+            "range.min" => float(self.range_min),
+            "range.max" => float(self.range_max),
+            "range.saturation" => string(self.saturation.to_string()),
+            "gain.mode" => string(self.gain_mode.to_string()),
+            "gain.percentile_low" => float(self.percentile_low),
+            "gain.percentile_high" => float(self.percentile_high),
+            "isotherm.mode" => string(self.isotherm_mode.to_string()),
+            "isotherm.min" => float(self.isotherm_min),
+            "isotherm.max" => float(self.isotherm_max),
+            "isotherm.color" => string(self.isotherm_color.to_string()),
+            "isotherm.gray" => Some(toml::Value::Boolean(self.isotherm_gray)),
+            "image.interpolation" => string(self.interpolation.to_string()),
+            "image.scale_factor" => Some(toml::Value::Integer(self.scale_factor as i64)),
+            "units.temperature" => string(self.unit.to_string()),
+            "storage.save_dir" => self.save_dir.clone().and_then(string),
+            "storage.save_format" => string(self.save_format.to_string()),
+            "storage.filename_template" => string(self.filename_template.clone()),
+            "storage.job" => string(self.job.clone()),
+            "storage.session" => self.session.clone().and_then(string),
+            "recording.format" => string(self.record_format.to_string()),
+            "recording.max_duration" => Some(toml::Value::Integer(self.record_duration as i64)),
+            "network.http_enabled" => Some(toml::Value::Boolean(self.http_enabled)),
+            "network.http_port" => Some(toml::Value::Integer(self.http_port as i64)),
+            "network.http_token" => string(self.http_token.clone()),
+            "network.tcp_enabled" => Some(toml::Value::Boolean(self.tcp_enabled)),
+            "network.tcp_port" => Some(toml::Value::Integer(self.tcp_port as i64)),
+            "mqtt.enabled" => Some(toml::Value::Boolean(self.mqtt_enabled)),
+            "mqtt.host" => string(self.mqtt_host.clone()),
+            "mqtt.port" => Some(toml::Value::Integer(self.mqtt_port as i64)),
+            "mqtt.username" => string(self.mqtt_username.clone()),
+            "mqtt.password" => string(self.mqtt_password.clone()),
+            "mqtt.topic" => string(self.mqtt_topic.clone()),
+            "mqtt.interval" => Some(toml::Value::Integer(self.mqtt_interval as i64)),
+            "mqtt.discovery_prefix" => string(self.mqtt_discovery_prefix.clone()),
+            "mqtt.alarm_min" => self.mqtt_alarm_min.and_then(float),
+            "mqtt.alarm_max" => self.mqtt_alarm_max.and_then(float),
+            "ui.left_handed" => Some(toml::Value::Boolean(self.left_handed)),
+            "ui.profile" => self.profile.clone().and_then(string),
+            _ => None,
+        };
+    }
+
+    fn to_table(&self, profile: bool) -> toml::Table {
+        let mut table = toml::Table::new();
+        table.insert("version".to_string(), toml::Value::Integer(SCHEMA_VERSION));
+
+        for (key, _) in KEYS.iter() {
+            if profile && DEVICE_KEYS.contains(key) { continue; }
+
+            let (section, name) = key.split_once('.').unwrap();
+
+            let value = match self.get_value(key) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            table
+                .entry(section)
+                .or_insert(toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .unwrap()
+                .insert(name.to_string(), value);
+        }
+
+        return table;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("thermal-camera-config-{}-{}", name, std::process::id()));
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        fs::create_dir_all(&dir).unwrap();
+
+        return dir;
+    }
+
+    fn assert_same(a: &Opt, b: &Opt) {
+        for (key, _) in KEYS.iter() {
+            assert_eq!(a.get_value(key), b.get_value(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn migrates_legacy_options() {
+        let opt = parse_legacy("color:Gray\nleft_hand:true\nrange_mode:manual\nrange_min:12.5\nsave_dir:/media/usb\nunknown:1\nbroken line\n");
+
+        assert_eq!(opt.get_value("palette.color"), Some(toml::Value::String("Gray".to_string())));
+        assert!(opt.left_handed);
+        assert_eq!(opt.range_mode, mlx::RangeModes::Manual);
+        assert_eq!(opt.range_min, 12.5);
+        assert_eq!(opt.save_dir.as_deref(), Some("/media/usb"));
+
+        // Everything not in the file keeps its default
+        assert_eq!(opt.range_max, Opt::default().range_max);
+
+        // The same keys in a version 0 config file
+        let mut opt = Opt::default();
+        assert!(apply_toml(&mut opt, "version = 0\nleft_hand = true\nrange_min = 12.5\nunknown = 1\n", false).is_empty());
+
+        assert!(opt.left_handed);
+        assert_eq!(opt.range_min, 12.5);
+
+        let migrated = migrate_v0(toml::from_str("isotherm_color = \"Cyan\"\nscale_factor = 4\n").unwrap());
+        assert_eq!(migrated["version"].as_integer(), Some(1));
+        assert_eq!(migrated["isotherm"]["color"].as_str(), Some("Cyan"));
+        assert_eq!(migrated["image"]["scale_factor"].as_integer(), Some(4));
+
+        assert!(migrate(toml::Table::new(), -1).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut opt = Opt::default();
+
+        for (key, val) in [
+            ("palette.color", "gray"),
+            ("range.mode", "manual"),
+            ("range.min", "-12.5"),
+            ("isotherm.gray", "true"),
+            ("image.scale_factor", "4"),
+            ("units.temperature", "fahrenheit"),
+            ("storage.save_dir", "/media/usb"),
+            ("storage.filename_template", "{job}_{seq}"),
+            ("recording.format", "apng"),
+            ("network.http_token", "abc_123-x"),
+            ("mqtt.alarm_max", "60.5"),
+            ("ui.profile", "roof"),
+        ] {
+            opt.set_value(key, val).unwrap();
+        }
+
+        let content = toml::to_string(&opt.to_table(false)).unwrap();
+
+        let mut res = Opt::default();
+        assert!(apply_toml(&mut res, &content, false).is_empty());
+        assert_same(&res, &opt);
+
+        // Profiles leave the settings of the device out
+        let profile = opt.to_table(true);
+        assert!(profile.get("network").is_none());
+        assert!(profile["storage"].get("save_dir").is_none());
+        assert!(profile["storage"].get("filename_template").is_some());
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        let mut opt = Opt::default();
+
+        let errors = apply_toml(&mut opt, "[range]\nmin = \"warm\"\nmax = 50.0\n[bogus]\nx = 1\n", false);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(opt.range_min, Opt::default().range_min);
+        assert_eq!(opt.range_max, 50.0);
+
+        assert!(opt.set_value("network.http_token", "a b").is_err());
+        assert!(opt.set_value("network.http_token", "<script>").is_err());
+    }
+
+    #[test]
+    fn environment_overrides_config_and_profile() {
+        let dir = test_dir("env");
+        let config_path = dir.join(CONFIG_FILE);
+
+        fs::write(&config_path, "version = 1\n[range]\nmin = 5.0\nmax = 45.0\n[isotherm]\nmin = 31.0\n").unwrap();
+        fs::create_dir_all(dir.join(PROFILE_DIR)).unwrap();
+        fs::write(dir.join(PROFILE_DIR).join("roof.toml"), "[range]\nmin = 6.0\n[isotherm]\nmin = 32.0\n").unwrap();
+
+        // Only used by this test, the environment is shared by all of them
+        env::set_var("THERMAL_CAMERA_ISOTHERM_MIN", "33");
+
+        let opt = load(&config_path, Some("roof"));
+
+        assert_eq!(opt.range_max, 45.0);
+        assert_eq!(opt.range_min, 6.0);
+        assert_eq!(opt.isotherm_min, 33.0);
+        assert_eq!(opt.profile.as_deref(), Some("roof"));
+
+        env::remove_var("THERMAL_CAMERA_ISOTHERM_MIN");
+
+        fs::write(dir.join("isotherm_max"), "44.5\n").unwrap();
+        env::set_var("THERMAL_CAMERA_ISOTHERM_MAX_FILE", dir.join("isotherm_max"));
+
+        let opt = load(&config_path, None);
+
+        assert_eq!(opt.range_min, 5.0);
+        assert_eq!(opt.isotherm_min, 31.0);
+        assert_eq!(opt.isotherm_max, 44.5);
+
+        env::remove_var("THERMAL_CAMERA_ISOTHERM_MAX_FILE");
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn writes_only_changed_settings() {
+        let dir = test_dir("update");
+        let config_path = dir.join(CONFIG_FILE);
+
+        fs::write(&config_path, "version = 1\n[range]\nmin = 5.0\n").unwrap();
+
+        // e.g. set through the environment, it mustn't end up in the file
+        let mut old = load(&config_path, None);
+        old.set_value("storage.job", "from_env").unwrap();
+
+        let mut new = old.clone();
+        new.set_value("range.max", "35").unwrap();
+
+        update_config(&config_path, &old, &new).unwrap();
+
+        let table: toml::Table = toml::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+        assert_eq!(table["range"]["min"].as_float(), Some(5.0));
+        assert_eq!(table["range"]["max"].as_float(), Some(35.0));
+        assert!(table.get("storage").is_none());
+
+        let mode = fs::metadata(&config_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, CONFIG_MODE);
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn keeps_profiles_in_their_directory() {
+        let dir = test_dir("profiles");
+        let config_path = dir.join(CONFIG_FILE);
+
+        fs::write(&config_path, "version = 1\n").unwrap();
+
+        let name = save_profile(&config_path, "Roof inspection", &Opt::default()).unwrap();
+        assert_eq!(name, "Roof_inspection");
+        assert_eq!(list_profiles(&config_path), vec![name.clone()]);
+
+        assert!(delete_profile(&config_path, "../config").is_err());
+        assert!(config_path.exists());
+
+        rename_profile(&config_path, &name, "attic").unwrap();
+        assert_eq!(list_profiles(&config_path), vec!["attic".to_string()]);
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
@@ -0,0 +1,445 @@
+use serde::{Serialize, Deserialize};
+use std::{fs, io::{self, Write}, path::Path, str::FromStr};
+use tiff::{encoder::{TiffEncoder, colortype}, tags::Tag};
+
+use super::super::{Opt, mlx};
+use super::bsp_storage;
+use mlx::{ImageRead, ScaledImage, TemperatureRead, PIXELS_WIDTH, PIXELS_HEIGHT, PIXEL_COUNT};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormats {
+    Png,
+    Tiff,
+    Csv,
+    Npy,
+    Annotated,
+}
+
+/*
+ * A measuring point on the image, position relative to width and height (0 - 1)
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub temperature: f32,
+}
+
+/*
+ * Everything needed to interpret a saved temperature grid later on,
+ * written as JSON sidecar next to the exported files
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameMetadata {
+    pub timestamp: String,
+    pub width: usize,
+    pub height: usize,
+    pub unit: String,
+    pub min_temp: f32,
+    pub max_temp: f32,
+    pub color_type: String,
+    pub color_range: (f32, f32),
+    pub framerate: String,
+    pub emissivity: f32,
+    pub serial: String,
+    #[serde(default)]
+    pub markers: Vec<Marker>,
+}
+
+impl FrameMetadata {
+    /*
+     * The unit is the one the image was read in, the GUI always uses Celsius
+     */
+    pub fn new(opt: &Opt, image: &ImageRead, unit: mlx::TemperatureUnits) -> Self {
+        Self {
+            timestamp: chrono::offset::Local::now().to_rfc3339(),
+            width: PIXELS_WIDTH,
+            height: PIXELS_HEIGHT,
+            unit: unit.symbol().to_string(),
+            min_temp: image.temperature_read.min_temp,
+            max_temp: image.temperature_read.max_temp,
+            color_type: opt.color_type.to_string(),
+            color_range: image.color_range,
+            framerate: opt.framerate.to_string(),
+            emissivity: mlx::get_emissivity(),
+            serial: mlx::get_serial().unwrap_or("Unknown".to_string()),
+            markers: Vec::new(),
+        }
+    }
+}
+
+impl ExportFormats {
+    pub fn from_path(file_path: &str) -> Result<Self, String> {
+        if file_path.to_lowercase().ends_with(".annotated.png") {
+            return Ok(ExportFormats::Annotated);
+        }
+
+        let extension = Path::new(file_path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            Some("png") => Ok(ExportFormats::Png),
+            Some("tif") | Some("tiff") => Ok(ExportFormats::Tiff),
+            Some("csv") => Ok(ExportFormats::Csv),
+            Some("npy") => Ok(ExportFormats::Npy),
+
+            _ => Err(format!("Unsupported file format: {}", file_path)),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormats::Png => "png",
+            ExportFormats::Tiff => "tiff",
+            ExportFormats::Csv => "csv",
+            ExportFormats::Npy => "npy",
+            ExportFormats::Annotated => "annotated.png",
+        }
+    }
+
+    /*
+     * Every file export writes, the picture first
+     */
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            ExportFormats::Png => &["png", "csv", "npy", "json"],
+            ExportFormats::Tiff => &["tiff", "json"],
+            ExportFormats::Csv => &["csv", "json"],
+            ExportFormats::Npy => &["npy", "json"],
+            ExportFormats::Annotated => &["annotated.png", "annotated.csv", "annotated.npy", "annotated.json"],
+        }
+    }
+}
+
+impl ToString for ExportFormats {
+    fn to_string(&self) -> String {
+        match self {
+            ExportFormats::Png => "PNG".into(),
+            ExportFormats::Tiff => "TIFF".into(),
+            ExportFormats::Csv => "CSV".into(),
+            ExportFormats::Npy => "NPY".into(),
+            ExportFormats::Annotated => "Annotated".into(),
+        }
+    }
+}
+
+impl FromStr for ExportFormats {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(ExportFormats::Png),
+            "tif" | "tiff" => Ok(ExportFormats::Tiff),
+            "csv" => Ok(ExportFormats::Csv),
+            "npy" => Ok(ExportFormats::Npy),
+            "annotated" => Ok(ExportFormats::Annotated),
+
+            _ => Err("Unrecognised export format")
+        }
+    }
+}
+
+/*
+ * Writes the image in the given format, every format gets a metadata sidecar.
+ * PNG additionally gets the temperatures as CSV and NumPy array,
+ * the annotated PNG is a report picture with scale and markers.
+ */
+pub fn export(file_path: &str, format: ExportFormats, image: &ImageRead, metadata: &FrameMetadata) -> io::Result<()> {
+    let path = Path::new(file_path);
+
+    match format {
+        ExportFormats::Png => {
+            let width = image.scaled.width as u32;
+            let height = image.scaled.height as u32;
+
+            super::write_png(file_path, &image.scaled.pixels, width, height, &image.temperature_read, metadata)?;
+            write_radiometric(file_path, &image.temperature_read, metadata)?;
+        },
+
+        ExportFormats::Tiff => {
+            write_tiff(path, &image.scaled, metadata)?;
+            write_metadata(&path.with_extension("json"), metadata)?;
+        },
+
+        ExportFormats::Csv => {
+            write_csv(path, &image.temperature_read)?;
+            write_metadata(&path.with_extension("json"), metadata)?;
+        },
+
+        ExportFormats::Npy => {
+            write_npy(path, &image.temperature_read)?;
+            write_metadata(&path.with_extension("json"), metadata)?;
+        },
+
+        ExportFormats::Annotated => {
+            let report = super::bsp_report::render_annotated(image, metadata);
+            let (width, height) = report.dimensions();
+
+            super::write_png(file_path, report.as_raw(), width, height, &image.temperature_read, metadata)?;
+            write_radiometric(file_path, &image.temperature_read, metadata)?;
+        },
+    }
+
+    return Ok(());
+}
+
+/*
+ * Exports in the configured format onto the storage found right now,
+ * the drive may have been swapped since the last capture.
+ * Returns the path saved to.
+ */
+pub fn save_capture(opt: &Opt, image: &ImageRead) -> io::Result<String> {
+    let storage = bsp_storage::find_save_target(opt.save_dir.as_deref())
+        .ok_or(io::Error::new(io::ErrorKind::NotFound, "No storage found"))?;
+
+    storage.check_space().map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    let path = super::get_save_path(&storage, opt, Some(image.temperature_read.max_temp), opt.save_format.extensions());
+    let metadata = FrameMetadata::new(opt, image, opt.unit);
+
+    export(&path, opt.save_format, image, &metadata)
+        .map_err(|err| io::Error::new(err.kind(), format!("Failed to write {}: {}", path, err)))?;
+
+    return Ok(path);
+}
+
+/*
+ * Writes CSV, NumPy array and metadata sidecar next to the given path
+ */
+pub fn write_radiometric(file_path: &str, temperatures: &TemperatureRead, metadata: &FrameMetadata) -> io::Result<()> {
+    let path = Path::new(file_path);
+
+    write_csv(&path.with_extension("csv"), temperatures)?;
+    write_npy(&path.with_extension("npy"), temperatures)?;
+    write_metadata(&path.with_extension("json"), metadata)?;
+
+    return Ok(());
+}
+
+/*
+ * One line per row, values in the unit of the metadata
+ */
+pub fn write_csv(path: &Path, temperatures: &TemperatureRead) -> io::Result<()> {
+    create_parent_dir(path)?;
+    return fs::write(path, grid_to_string(temperatures, Some(2)));
+}
+
+/*
+ * Comma separated values, one line per row.
+ * Without precision the values are written losslessly.
+ */
+pub fn grid_to_string(temperatures: &TemperatureRead, precision: Option<usize>) -> String {
+    let mut buf = String::new();
+
+    for row in temperatures.temperature_grid.chunks(PIXELS_WIDTH) {
+        let line: Vec<String> = row
+            .iter()
+            .map(|t| match precision {
+                Some(p) => format!("{:.*}", p, t),
+                None => t.to_string(),
+            })
+            .collect();
+
+        buf += &line.join(",");
+        buf += "\n";
+    }
+
+    return buf;
+}
+
+pub fn grid_from_string(s: &str) -> Result<TemperatureRead, String> {
+    let values: Vec<f32> = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|err| format!("Invalid temperature value: {}", err))?;
+
+    if values.len() != PIXEL_COUNT {
+        return Err(format!("Expected {} temperatures, got {}", PIXEL_COUNT, values.len()));
+    }
+
+    let mut res = TemperatureRead::default();
+    res.temperature_grid.copy_from_slice(&values);
+    res.min_temp = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    res.max_temp = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+    return Ok(res);
+}
+
+/*
+ * NumPy .npy version 1.0, little endian float32 with shape (height, width)
+ */
+pub fn write_npy(path: &Path, temperatures: &TemperatureRead) -> io::Result<()> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        PIXELS_HEIGHT,
+        PIXELS_WIDTH
+    );
+
+    // Data has to start on a multiple of 64 bytes, header ends with a newline
+    let unpadded_len = MAGIC.len() + 2 + header.len() + 1;
+    let padding = (64 - unpadded_len % 64) % 64;
+    header += &" ".repeat(padding);
+    header += "\n";
+
+    let mut buf: Vec<u8> = Vec::with_capacity(MAGIC.len() + 2 + header.len() + PIXEL_COUNT * 4);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    buf.extend_from_slice(header.as_bytes());
+
+    for t in temperatures.temperature_grid.iter() {
+        buf.extend_from_slice(&t.to_le_bytes());
+    }
+
+    create_parent_dir(path)?;
+    let mut f = fs::File::create(path)?;
+    f.write_all(&buf)?;
+
+    return Ok(());
+}
+
+/*
+ * Reads a temperature grid written by write_npy
+ */
+pub fn read_npy(path: &Path) -> io::Result<TemperatureRead> {
+    let buf = fs::read(path)?;
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    if buf.len() < 10 || &buf[0..6] != b"\x93NUMPY" {
+        return Err(invalid("Not a NumPy file"));
+    }
+
+    let header_len = u16::from_le_bytes([buf[8], buf[9]]) as usize;
+    let header = std::str::from_utf8(buf.get(10..10 + header_len).unwrap_or_default())
+        .map_err(|_| invalid("Invalid NumPy header"))?;
+
+    if !header.contains("'<f4'") || header.contains("'fortran_order': True") {
+        return Err(invalid("Only little endian float32 in C order is supported"));
+    }
+
+    let data = &buf[10 + header_len..];
+    if data.len() != PIXEL_COUNT * 4 {
+        return Err(invalid("Unexpected temperature count"));
+    }
+
+    let mut res = TemperatureRead::default();
+
+    for (t, bytes) in res.temperature_grid.iter_mut().zip(data.chunks(4)) {
+        *t = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    res.min_temp = res.temperature_grid.iter().cloned().fold(f32::INFINITY, f32::min);
+    res.max_temp = res.temperature_grid.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+    return Ok(res);
+}
+
+/*
+ * Single channel float32 TIFF of the (interpolated) temperatures
+ */
+pub fn write_tiff(path: &Path, image: &ScaledImage, metadata: &FrameMetadata) -> io::Result<()> {
+    create_parent_dir(path)?;
+    let f = fs::File::create(path)?;
+
+    let to_io_error = |err: tiff::TiffError| io::Error::new(io::ErrorKind::Other, err.to_string());
+
+    // TIFF only allows ASCII text
+    let unit_name = mlx::TemperatureUnits::from_symbol(&metadata.unit)
+        .unwrap_or(mlx::TemperatureUnits::Celsius)
+        .to_string();
+
+    let description = format!(
+        "Temperature in {}, emissivity {:.2}, sensor {}",
+        unit_name,
+        metadata.emissivity,
+        metadata.serial
+    );
+
+    let date_time = chrono::DateTime::parse_from_rfc3339(&metadata.timestamp)
+        .map(|t| t.format("%Y:%m:%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+
+    let mut encoder = TiffEncoder::new(io::BufWriter::new(f)).map_err(to_io_error)?;
+    let mut tiff_image = encoder
+        .new_image::<colortype::Gray32Float>(image.width as u32, image.height as u32)
+        .map_err(to_io_error)?;
+
+    tiff_image.encoder().write_tag(Tag::ImageDescription, description.as_str()).map_err(to_io_error)?;
+    tiff_image.encoder().write_tag(Tag::DateTime, date_time.as_str()).map_err(to_io_error)?;
+    tiff_image.encoder().write_tag(Tag::Software, "thermal-camera").map_err(to_io_error)?;
+
+    tiff_image.write_data(&image.temperatures).map_err(to_io_error)?;
+
+    return Ok(());
+}
+
+/*
+ * Reads the sensor grid back from a TIFF written by write_tiff. A scaled TIFF
+ * is sampled where the sensor pixel centers are, which gives the original
+ * temperatures with nearest interpolation and close to them otherwise.
+ */
+pub fn read_tiff(path: &Path) -> io::Result<TemperatureRead> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut decoder = tiff::decoder::Decoder::new(io::BufReader::new(fs::File::open(path)?))
+        .map_err(|err| invalid(err.to_string()))?;
+
+    let (width, height) = decoder.dimensions().map_err(|err| invalid(err.to_string()))?;
+    let (width, height) = (width as usize, height as usize);
+
+    let factor = width / PIXELS_WIDTH;
+    if factor == 0 || width != PIXELS_WIDTH * factor || height != PIXELS_HEIGHT * factor {
+        return Err(invalid(format!("Unexpected TIFF size {} x {}", width, height)));
+    }
+
+    let data = match decoder.read_image().map_err(|err| invalid(err.to_string()))? {
+        tiff::decoder::DecodingResult::F32(data) => data,
+        _ => return Err(invalid("Only float32 TIFFs are supported".to_string())),
+    };
+
+    // With an even factor the center lies between two output pixels
+    let offsets = [(factor - 1) / 2, factor / 2];
+
+    let mut res = TemperatureRead::default();
+
+    for y in 0..PIXELS_HEIGHT {
+        for x in 0..PIXELS_WIDTH {
+            let mut sum = 0.0;
+
+            for dy in offsets {
+                for dx in offsets {
+                    sum += data[(y * factor + dy) * width + x * factor + dx];
+                }
+            }
+
+            res.temperature_grid[y * PIXELS_WIDTH + x] = sum / 4.0;
+        }
+    }
+
+    res.min_temp = res.temperature_grid.iter().cloned().fold(f32::INFINITY, f32::min);
+    res.max_temp = res.temperature_grid.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+    return Ok(res);
+}
+
+pub fn write_metadata(path: &Path, metadata: &FrameMetadata) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(metadata)?;
+
+    create_parent_dir(path)?;
+    return fs::write(path, json);
+}
+
+fn create_parent_dir(path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    return Ok(());
+}
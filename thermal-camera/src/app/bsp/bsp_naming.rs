@@ -0,0 +1,257 @@
+use std::{fs, path::Path};
+
+pub const DEFAULT_TEMPLATE: &str = "{date}_{time}";
+
+// Stem suffix of the annotated report picture, "x.annotated.png" is the report of "x.png"
+const ANNOTATED_SUFFIX: &str = ".annotated";
+
+/*
+ * Values for the tokens of a filename template:
+ * {date}    - 2024-01-31
+ * {time}    - 12-00-00
+ * {seq}     - 0001, one more than the highest number in the directory
+ * {job}     - job or site name
+ * {session} - name of the running session
+ * {max}     - highest temperature, e.g. 36.5C
+ */
+pub struct Naming<'a> {
+    pub template: &'a str,
+    pub job: &'a str,
+    pub session: Option<&'a str>,
+    pub max_temp: Option<f32>,
+}
+
+/*
+ * Full path for a new capture with the first of the extensions, the others
+ * are those of the files saved next to it. Sessions get their own folder.
+ * None of the files may exist yet, a counter is appended if the template
+ * alone doesn't make the name unique.
+ */
+pub fn get_file_path(dir: &str, naming: &Naming, extensions: &[&str]) -> String {
+    let t = chrono::offset::Local::now();
+
+    let dir = match naming.session.map(sanitize) {
+        Some(session) if !session.is_empty() => format!("{}/{}", dir, session),
+        _ => dir.to_string(),
+    };
+
+    let template = if naming.template.trim().is_empty() { DEFAULT_TEMPLATE } else { naming.template };
+
+    let name = template
+        .replace("{date}", &t.format("%Y-%m-%d").to_string())
+        .replace("{time}", &t.format("%H-%M-%S").to_string())
+        .replace("{job}", &sanitize(naming.job))
+        .replace("{session}", &sanitize(naming.session.unwrap_or_default()))
+        .replace("{max}", &naming.max_temp.map(|m| format!("{:.1}C", m)).unwrap_or_default());
+
+    let name = sanitize(&name);
+    let extension = extensions.first().copied().unwrap_or_default();
+
+    let stem = match name.split_once("{seq}") {
+        Some((prefix, suffix)) => {
+            let seq = get_highest_seq(&dir, prefix, suffix) + 1;
+            name.replace("{seq}", &format!("{:04}", seq))
+        },
+
+        None => name,
+    };
+
+    return format!("{}/{}.{}", dir, get_unique_stem(&dir, &stem, extensions), extension);
+}
+
+/*
+ * Highest number of the files named "<prefix><number><suffix>.<extension>" in dir, 0 if there are none
+ */
+fn get_highest_seq(dir: &str, prefix: &str, suffix: &str) -> usize {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    return entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let rest = file_name.strip_prefix(prefix)?;
+
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let seq = rest[..digits].parse::<usize>().ok()?;
+
+            return match rest[digits..].strip_prefix(suffix) {
+                Some(end) if end.starts_with('.') => Some(seq),
+                _ => None,
+            };
+        })
+        .max()
+        .unwrap_or(0);
+}
+
+/*
+ * The stem itself if no file with it and one of the extensions exists in dir,
+ * otherwise with the lowest free counter appended, e.g. "x_2". The counter goes
+ * before ".annotated", so the report stays recognizable.
+ */
+pub fn get_unique_stem<S: AsRef<str>>(dir: &str, stem: &str, extensions: &[S]) -> String {
+    let (base, suffix) = match stem.strip_suffix(ANNOTATED_SUFFIX) {
+        Some(base) => (base, ANNOTATED_SUFFIX),
+        None => (stem, ""),
+    };
+
+    for i in 1.. {
+        let candidate = match i {
+            1 => stem.to_string(),
+            _ => format!("{}_{}{}", base, i, suffix),
+        };
+
+        let taken = extensions.iter().any(|extension| {
+            let extension = extension.as_ref();
+            let file_name = if extension.is_empty() { candidate.clone() } else { format!("{}.{}", candidate, extension) };
+
+            return Path::new(dir).join(file_name).exists();
+        });
+
+        if !taken {
+            return candidate;
+        }
+    }
+
+    unreachable!();
+}
+
+/*
+ * Keeps names usable as a single path component on any filesystem
+ */
+pub fn sanitize(name: &str) -> String {
+    let res: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '{' | '}' => c,
+            _ => '_',
+        })
+        .collect();
+
+    // "." and ".." would point to another directory
+    if res.chars().all(|c| c == '.') {
+        return res.replace('.', "_");
+    }
+
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::bsp_export::ExportFormats;
+
+    fn test_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("thermal-camera-naming-{}-{}", name, std::process::id()));
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        fs::create_dir_all(&dir).unwrap();
+
+        return dir.to_string_lossy().to_string();
+    }
+
+    fn naming(template: &str) -> Naming<'_> {
+        Naming {
+            template,
+            job: "Roof A/B",
+            session: None,
+            max_temp: Some(36.54),
+        }
+    }
+
+    #[test]
+    fn expands_template() {
+        let dir = test_dir("template");
+
+        let path = get_file_path(&dir, &naming("{job}_{max}"), &["png"]);
+        assert_eq!(path, format!("{}/Roof_A_B_36.5C.png", dir));
+
+        let session = Naming { session: Some("site 1"), ..naming("{session}-{job}") };
+        assert_eq!(get_file_path(&dir, &session, &["csv"]), format!("{}/site_1/site_1-Roof_A_B.csv", dir));
+
+        // Empty falls back to the default "{date}_{time}", e.g. "2024-01-31_12-00-00"
+        let path = get_file_path(&dir, &naming(" "), &["png"]);
+        let stem = Path::new(&path).file_stem().unwrap().to_string_lossy().to_string();
+
+        assert_eq!(stem.len(), 19);
+        assert!(chrono::NaiveDateTime::parse_from_str(&stem, "%Y-%m-%d_%H-%M-%S").is_ok());
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn continues_after_highest_seq() {
+        let dir = test_dir("seq");
+        let template = naming("{job}_{seq}");
+
+        let first = get_file_path(&dir, &template, &["png"]);
+        assert_eq!(first, format!("{}/Roof_A_B_0001.png", dir));
+
+        fs::write(&first, "").unwrap();
+        fs::write(format!("{}/Roof_A_B_0003.png", dir), "").unwrap();
+        assert_eq!(get_file_path(&dir, &template, &["png"]), format!("{}/Roof_A_B_0004.png", dir));
+
+        // Freed numbers aren't used again, whatever the extension
+        fs::remove_file(&first).unwrap();
+        fs::write(format!("{}/Roof_A_B_0004.json", dir), "").unwrap();
+        assert_eq!(get_file_path(&dir, &template, &["tiff", "json"]), format!("{}/Roof_A_B_0005.tiff", dir));
+
+        // Other names don't count
+        fs::write(format!("{}/Roof_A_B_0009_x.png", dir), "").unwrap();
+        fs::write(format!("{}/Other_0010.png", dir), "").unwrap();
+        assert_eq!(get_file_path(&dir, &template, &["png"]), format!("{}/Roof_A_B_0005.png", dir));
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn makes_names_unique() {
+        let dir = test_dir("unique");
+
+        assert_eq!(get_file_path(&dir, &naming("{job}"), &["png"]), format!("{}/Roof_A_B.png", dir));
+
+        fs::write(format!("{}/Roof_A_B.png", dir), "").unwrap();
+        assert_eq!(get_file_path(&dir, &naming("{job}"), &["png"]), format!("{}/Roof_A_B_2.png", dir));
+
+        fs::write(format!("{}/x.json", dir), "").unwrap();
+        fs::write(format!("{}/x.annotated.png", dir), "").unwrap();
+
+        assert_eq!(get_unique_stem(&dir, "x", &["png"]), "x");
+        assert_eq!(get_unique_stem(&dir, "x", &["png", "json"]), "x_2");
+        assert_eq!(get_unique_stem(&dir, "x.annotated", &["png"]), "x_2.annotated");
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn keeps_sidecars_of_other_formats() {
+        let dir = test_dir("sidecars");
+        let template = naming("{job}");
+
+        fs::write(format!("{}/Roof_A_B.png", dir), "").unwrap();
+        fs::write(format!("{}/Roof_A_B.json", dir), "").unwrap();
+
+        // A TIFF of the same name would overwrite the metadata of the PNG
+        let tiff = ExportFormats::Tiff.extensions();
+        assert_eq!(get_file_path(&dir, &template, tiff), format!("{}/Roof_A_B_2.tiff", dir));
+
+        let annotated = ExportFormats::Annotated.extensions();
+        assert_eq!(get_file_path(&dir, &template, annotated), format!("{}/Roof_A_B.annotated.png", dir));
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(sanitize(" Roof inspection "), "Roof_inspection");
+        assert_eq!(sanitize("a/b\\c:d"), "a_b_c_d");
+        assert_eq!(sanitize("../config"), ".._config");
+        assert_eq!(sanitize(".."), "__");
+        assert_eq!(sanitize("."), "_");
+        assert_eq!(sanitize("{date}_{seq}.v2"), "{date}_{seq}.v2");
+        assert_eq!(sanitize("Größe"), "Gr__e");
+    }
+}
@@ -0,0 +1,343 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::super::mlx;
+use mlx::{Framerates, ScaledImage};
+
+// Upper limit for the recording length in seconds
+pub const MAX_DURATION: u32 = 300;
+
+// Recordings are written to this extension first, renamed once finished
+pub const PARTIAL_EXTENSION: &str = "part";
+
+// Speed of the GIF color quantization, 1 is best quality, 30 is fastest
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+// Frames waiting for the encoder, more are skipped so memory stays bounded
+const QUEUE_CAPACITY: usize = 8;
+
+const PNG_SIGNATURE_LEN: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormats {
+    Gif,
+    Apng,
+}
+
+/*
+ * Live view frames, encoded on a writer thread while recording, so only a few
+ * frames are held in memory. A frame the encoder can't keep up with is skipped and
+ * the one before is shown longer, so the clip still plays back in real time.
+ */
+pub struct Recording {
+    pub format: RecordFormats,
+    pub max_temp: f32,
+    frame_period: Duration,
+    max_frames: usize,
+    // Frames since the start, skipped ones included
+    frame_count: usize,
+    width: usize,
+    height: usize,
+    partial_path: PathBuf,
+    frame_tx: Option<SyncSender<(usize, Vec<u8>)>>,
+    writer: Option<JoinHandle<io::Result<u32>>>,
+}
+
+impl Recording {
+    /*
+     * The partial file goes into dir, the final name is only known when stopping
+     */
+    pub fn new(format: RecordFormats, framerate: Framerates, max_duration: u32, dir: &str) -> Self {
+        let frame_period = framerate.frame_period();
+        let max_frames = (max_duration as f32 / frame_period.as_secs_f32()).ceil() as usize;
+
+        let file_name = format!(".recording_{}.{}", chrono::offset::Local::now().format("%Y%m%d_%H%M%S"), PARTIAL_EXTENSION);
+
+        Self {
+            format,
+            max_temp: f32::NEG_INFINITY,
+            frame_period,
+            max_frames: max_frames.max(1),
+            frame_count: 0,
+            width: 0,
+            height: 0,
+            partial_path: Path::new(dir).join(file_name),
+            frame_tx: None,
+            writer: None,
+        }
+    }
+
+    /*
+     * Returns false once the maximum duration is reached or writing failed.
+     * Frames with a different size than the first one are skipped.
+     */
+    pub fn add_frame(&mut self, image: &ScaledImage) -> bool {
+        if self.is_full() { return false; }
+
+        if self.frame_tx.is_none() {
+            if self.writer.is_some() { return false; }
+
+            self.width = image.width;
+            self.height = image.height;
+
+            if let Err(err) = self.start_writer() {
+                eprintln!("Failed to start recording: {}", err);
+                return false;
+            }
+        }
+
+        if image.width == self.width && image.height == self.height {
+            let res = self.frame_tx.as_ref().unwrap().try_send((self.frame_count, image.pixels.clone()));
+
+            // The writer stopped, finish() reports why
+            if let Err(TrySendError::Disconnected(_)) = res {
+                self.frame_tx = None;
+                return false;
+            }
+
+            self.max_temp = image.temperatures.iter().cloned().fold(self.max_temp, f32::max);
+            self.frame_count += 1;
+        }
+
+        return !self.is_full();
+    }
+
+    pub fn is_full(&self) -> bool {
+        return self.frame_count >= self.max_frames;
+    }
+
+    pub fn duration(&self) -> Duration {
+        return self.frame_period * self.frame_count as u32;
+    }
+
+    /*
+     * Waits for the encoder and moves the clip to its final path
+     */
+    pub fn finish(mut self, file_path: &str) -> io::Result<()> {
+        // Closing the queue ends the writer
+        self.frame_tx = None;
+
+        let res = match self.writer.take() {
+            Some(writer) => writer.join()
+                .unwrap_or(Err(io::Error::new(io::ErrorKind::Other, "Encoder crashed")))
+                .and_then(|frames| self.complete(frames, Path::new(file_path))),
+            None => Err(io::Error::new(io::ErrorKind::Other, "Recording contains no frames")),
+        };
+
+        if res.is_err() {
+            fs::remove_file(&self.partial_path).unwrap_or_default();
+        }
+
+        return res;
+    }
+
+    fn start_writer(&mut self) -> io::Result<()> {
+        if let Some(parent) = self.partial_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let f = io::BufWriter::new(File::create(&self.partial_path)?);
+
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let (format, width, height, frame_period, max_frames) = (self.format, self.width, self.height, self.frame_period, self.max_frames);
+
+        self.writer = Some(thread::spawn(move || match format {
+            RecordFormats::Gif => write_gif(f, width, height, frame_period, rx),
+            RecordFormats::Apng => write_apng(f, width, height, frame_period, max_frames, rx),
+        }));
+        self.frame_tx = Some(tx);
+
+        return Ok(());
+    }
+
+    fn complete(&self, frames: u32, path: &Path) -> io::Result<()> {
+        if self.format == RecordFormats::Apng {
+            set_apng_frame_count(&self.partial_path, frames)?;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // The storage may have changed while recording, copy if it's a different filesystem
+        if fs::rename(&self.partial_path, path).is_err() {
+            fs::copy(&self.partial_path, path)?;
+            fs::remove_file(&self.partial_path)?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        // Not finished, e.g. the GUI closed while recording
+        if self.writer.is_some() {
+            self.frame_tx = None;
+            self.writer.take().map(|writer| writer.join());
+            fs::remove_file(&self.partial_path).unwrap_or_default();
+        }
+    }
+}
+
+/*
+ * Hands every frame to write together with its display time, which is known once
+ * the next frame arrived. Returns the number of frames written.
+ */
+fn write_timed(rx: Receiver<(usize, Vec<u8>)>, frame_period: Duration, mut write: impl FnMut(&[u8], Duration) -> io::Result<()>) -> io::Result<u32> {
+    let mut previous: Option<(usize, Vec<u8>)> = None;
+    let mut written = 0;
+
+    for (index, pixels) in rx.iter() {
+        if let Some((previous_index, previous_pixels)) = previous.replace((index, pixels)) {
+            write(&previous_pixels, frame_period * (index - previous_index) as u32)?;
+            written += 1;
+        }
+    }
+
+    if let Some((_, pixels)) = previous {
+        write(&pixels, frame_period)?;
+        written += 1;
+    }
+
+    return Ok(written);
+}
+
+fn write_gif<W: Write>(w: W, width: usize, height: usize, frame_period: Duration, rx: Receiver<(usize, Vec<u8>)>) -> io::Result<u32> {
+    let to_io_error = |err: gif::EncodingError| io::Error::new(io::ErrorKind::Other, err.to_string());
+
+    let mut encoder = gif::Encoder::new(w, width as u16, height as u16, &[]).map_err(to_io_error)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(to_io_error)?;
+
+    let frames = write_timed(rx, frame_period, |pixels, duration| {
+        let mut frame = gif::Frame::from_rgb_speed(width as u16, height as u16, pixels, GIF_QUANTIZE_SPEED);
+
+        // GIF delays are in 1/100 s
+        frame.delay = (duration.as_millis() / 10).max(1).min(u16::MAX as u128) as u16;
+
+        return encoder.write_frame(&frame).map_err(to_io_error);
+    })?;
+
+    encoder.into_inner()?.flush()?;
+
+    return Ok(frames);
+}
+
+/*
+ * The frame count has to be announced in the header, so the maximum is given there
+ * and corrected by set_apng_frame_count once the recording is complete
+ */
+fn write_apng<W: Write>(w: W, width: usize, height: usize, frame_period: Duration, max_frames: usize, rx: Receiver<(usize, Vec<u8>)>) -> io::Result<u32> {
+    let to_io_error = |err: png::EncodingError| io::Error::new(io::ErrorKind::Other, err.to_string());
+
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(max_frames as u32, 0).map_err(to_io_error)?;
+
+    encoder.add_text_chunk("Software".to_string(), "thermal-camera".to_string()).map_err(to_io_error)?;
+
+    let mut writer = encoder.write_header().map_err(to_io_error)?;
+
+    let frames = write_timed(rx, frame_period, |pixels, duration| {
+        let delay = duration.as_millis().min(u16::MAX as u128) as u16;
+
+        writer.set_frame_delay(delay, 1000).map_err(to_io_error)?;
+        return writer.write_image_data(pixels).map_err(to_io_error);
+    })?;
+
+    writer.finish().map_err(to_io_error)?;
+
+    return Ok(frames);
+}
+
+/*
+ * Rewrites num_frames of the acTL chunk and its checksum
+ */
+fn set_apng_frame_count(path: &Path, frames: u32) -> io::Result<()> {
+    let mut f = fs::OpenOptions::new().read(true).write(true).open(path)?;
+    let mut offset = PNG_SIGNATURE_LEN;
+
+    loop {
+        let mut chunk_header = [0u8; 8];
+        f.seek(SeekFrom::Start(offset))?;
+        f.read_exact(&mut chunk_header)?;
+
+        let length = u32::from_be_bytes(chunk_header[0..4].try_into().unwrap()) as u64;
+        let chunk_type = &chunk_header[4..8];
+
+        if chunk_type == b"acTL" {
+            let mut data = [0u8; 8];
+            f.read_exact(&mut data)?;
+            data[0..4].copy_from_slice(&frames.to_be_bytes());
+
+            let crc = crc32(&[chunk_type, &data].concat());
+
+            f.seek(SeekFrom::Start(offset + 8))?;
+            f.write_all(&data)?;
+            f.write_all(&crc.to_be_bytes())?;
+
+            return Ok(());
+        }
+
+        if chunk_type == b"IDAT" || chunk_type == b"IEND" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "No animation control chunk found"));
+        }
+
+        // Length, type, data and checksum
+        offset += 12 + length;
+    }
+}
+
+/*
+ * CRC-32 of PNG chunks (ISO 3309)
+ */
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    return !crc;
+}
+
+impl RecordFormats {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormats::Gif => "gif",
+            RecordFormats::Apng => "apng",
+        }
+    }
+}
+
+impl ToString for RecordFormats {
+    fn to_string(&self) -> String {
+        match self {
+            RecordFormats::Gif => "GIF".into(),
+            RecordFormats::Apng => "APNG".into(),
+        }
+    }
+}
+
+impl FromStr for RecordFormats {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gif" => Ok(RecordFormats::Gif),
+            "apng" => Ok(RecordFormats::Apng),
+
+            _ => Err("Unrecognised record format")
+        }
+    }
+}
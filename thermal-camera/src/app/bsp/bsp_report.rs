@@ -0,0 +1,236 @@
+use image::{RgbImage, Rgb};
+
+use super::super::mlx;
+use mlx::{ImageRead, GRADIENT_HEIGHT, GRADIENT_WIDTH, SCALE_TICK_COUNT};
+use super::bsp_export::FrameMetadata;
+
+// The thermal image gets enlarged to at least this width
+const MIN_IMAGE_WIDTH: u32 = 480;
+
+const MARGIN: u32 = 10;
+const SCALE_WIDTH: u32 = 20;
+const SCALE_LABEL_WIDTH: u32 = 8 * GLYPH_ADVANCE;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT * FONT_SCALE + 6;
+const TEXT_LINES: u32 = 3;
+const MARKER_SIZE: i32 = 8;
+
+const FONT_SCALE: u32 = 2;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const GLYPH_ADVANCE: u32 = (GLYPH_WIDTH + 1) * FONT_SCALE;
+
+const COL_BACKGROUND: Rgb<u8> = Rgb([0x00, 0x00, 0x00]);
+const COL_TEXT: Rgb<u8> = Rgb([0xFF, 0xFF, 0xFF]);
+
+/*
+ * Renders the thermal image together with its scale, temperatures,
+ * capture information and markers into a single picture for reports
+ */
+pub fn render_annotated(image: &ImageRead, metadata: &FrameMetadata) -> RgbImage {
+    let scaled = &image.scaled;
+
+    let zoom = (MIN_IMAGE_WIDTH + scaled.width as u32 - 1) / scaled.width as u32;
+    let img_width = scaled.width as u32 * zoom;
+    let img_height = scaled.height as u32 * zoom;
+
+    let width = MARGIN + img_width + MARGIN + SCALE_WIDTH + 4 + SCALE_LABEL_WIDTH + MARGIN;
+    let height = MARGIN + img_height + MARGIN + TEXT_LINES * LINE_HEIGHT + MARGIN;
+
+    let mut canvas = RgbImage::from_pixel(width, height, COL_BACKGROUND);
+
+    // Thermal image
+    for y in 0..img_height {
+        for x in 0..img_width {
+            let index = ((y / zoom) as usize * scaled.width + (x / zoom) as usize) * 3;
+            let color = Rgb([scaled.pixels[index], scaled.pixels[index + 1], scaled.pixels[index + 2]]);
+
+            canvas.put_pixel(MARGIN + x, MARGIN + y, color);
+        }
+    }
+
+    let unit = metadata.unit.as_str();
+
+    draw_scale(&mut canvas, image, unit, MARGIN + img_width + MARGIN, MARGIN, img_height);
+
+    // Markers
+    let centre_temp = scaled.get_temperature(0.5, 0.5);
+    draw_crosshair(&mut canvas, MARGIN + img_width / 2, MARGIN + img_height / 2);
+
+    for marker in metadata.markers.iter() {
+        let x = MARGIN + (marker.x * img_width as f32).min(img_width as f32 - 1.0) as u32;
+        let y = MARGIN + (marker.y * img_height as f32).min(img_height as f32 - 1.0) as u32;
+
+        draw_crosshair(&mut canvas, x, y);
+        draw_text_boxed(&mut canvas, &format!("{:.1}{}", marker.temperature, unit), x + 6, y + 6);
+    }
+
+    // Text
+    let text_top = MARGIN + img_height + MARGIN;
+
+    let line_temperatures = format!(
+        "MAX {:.1}{}   MIN {:.1}{}   CENTRE {:.1}{}",
+        image.temperature_read.max_temp, unit,
+        image.temperature_read.min_temp, unit,
+        centre_temp, unit
+    );
+
+    let line_markers: Vec<String> = metadata.markers
+        .iter()
+        .map(|m| format!("{} {:.1}{}", m.name, m.temperature, unit))
+        .collect();
+
+    let time = chrono::DateTime::parse_from_rfc3339(&metadata.timestamp)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or(metadata.timestamp.clone());
+
+    let line_info = format!("{}   EMISSIVITY {:.2}   {}", time, metadata.emissivity, metadata.color_type);
+
+    draw_text(&mut canvas, &line_temperatures, MARGIN, text_top);
+    draw_text(&mut canvas, &line_markers.join("   "), MARGIN, text_top + LINE_HEIGHT);
+    draw_text(&mut canvas, &line_info, MARGIN, text_top + LINE_HEIGHT * 2);
+
+    return canvas;
+}
+
+fn draw_scale(canvas: &mut RgbImage, image: &ImageRead, unit: &str, left: u32, top: u32, height: u32) {
+    let gradient = mlx::get_scale(&image.settings, &image.scale_ticks);
+
+    for y in 0..height {
+        let gradient_y = (y as usize * GRADIENT_HEIGHT / height as usize).min(GRADIENT_HEIGHT - 1);
+        let index = gradient_y * GRADIENT_WIDTH * 3;
+        let color = Rgb([gradient[index], gradient[index + 1], gradient[index + 2]]);
+
+        for x in 0..SCALE_WIDTH {
+            canvas.put_pixel(left + x, top + y, color);
+        }
+    }
+
+    // Tick labels, scale_ticks go from bottom to top
+    let label_height = GLYPH_HEIGHT * FONT_SCALE;
+
+    for (i, tick) in image.scale_ticks.iter().enumerate() {
+        let t = i as f32 / (SCALE_TICK_COUNT - 1) as f32;
+        let y = top + ((1.0 - t) * (height - 1) as f32) as u32;
+
+        for x in 0..4 {
+            canvas.put_pixel(left + SCALE_WIDTH + x, y, COL_TEXT);
+        }
+
+        let label_y = y.saturating_sub(label_height / 2).max(top).min(top + height - label_height);
+        draw_text(canvas, &format!("{:.1}{}", tick, unit), left + SCALE_WIDTH + 6, label_y);
+    }
+}
+
+fn draw_crosshair(canvas: &mut RgbImage, x: u32, y: u32) {
+    for d in -MARKER_SIZE..=MARKER_SIZE {
+        // Leave a gap in the middle, so the marked pixel stays visible
+        if d.abs() < 2 { continue; }
+
+        put_pixel_checked(canvas, x as i32 + d, y as i32, COL_TEXT);
+        put_pixel_checked(canvas, x as i32, y as i32 + d, COL_TEXT);
+    }
+}
+
+fn draw_text_boxed(canvas: &mut RgbImage, text: &str, x: u32, y: u32) {
+    let width = text.chars().count() as u32 * GLYPH_ADVANCE;
+    let height = GLYPH_HEIGHT * FONT_SCALE;
+
+    for by in y.saturating_sub(2)..y + height + 2 {
+        for bx in x.saturating_sub(2)..x + width + 2 {
+            put_pixel_checked(canvas, bx as i32, by as i32, COL_BACKGROUND);
+        }
+    }
+
+    draw_text(canvas, text, x, y);
+}
+
+fn draw_text(canvas: &mut RgbImage, text: &str, x: u32, y: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph = get_glyph(c);
+        let glyph_x = x + i as u32 * GLYPH_ADVANCE;
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> col) == 0 { continue; }
+
+                for sy in 0..FONT_SCALE {
+                    for sx in 0..FONT_SCALE {
+                        let px = glyph_x + col * FONT_SCALE + sx;
+                        let py = y + row as u32 * FONT_SCALE + sy;
+                        put_pixel_checked(canvas, px as i32, py as i32, COL_TEXT);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn put_pixel_checked(canvas: &mut RgbImage, x: i32, y: i32, color: Rgb<u8>) {
+    if x < 0 || y < 0 || x >= canvas.width() as i32 || y >= canvas.height() as i32 {
+        return;
+    }
+
+    canvas.put_pixel(x as u32, y as u32, color);
+}
+
+/*
+ * 5x7 bitmap font, one byte per row from top to bottom,
+ * the highest of the five bits is the leftmost pixel
+ */
+fn get_glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '°' => [0x0C, 0x12, 0x12, 0x0C, 0x00, 0x00, 0x00],
+
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
@@ -0,0 +1,303 @@
+use std::{env, ffi::CString, fs, io, os::unix::fs::MetadataExt, path::{Path, PathBuf}};
+
+use super::{bsp_naming, bsp_record};
+
+const MOUNTS_PATH: &str = "/proc/mounts";
+
+// Captures are kept in $XDG_DATA_HOME/thermal-camera/captures while no other storage is available
+const INTERNAL_DIR: &str = "captures";
+
+// Captures go into this directory on the detected storage
+const CAPTURE_DIR: &str = "thermal-camera";
+
+// Saving is disabled below this, a single capture with sidecars is well below 1 MB
+pub const MIN_FREE_BYTES: u64 = 10 * 1024 * 1024;
+
+// Mount points where removable storage usually ends up
+const REMOVABLE_MOUNT_PREFIXES: [&str; 3] = ["/media/", "/run/media/", "/mnt/"];
+
+/*
+ * Where captures are saved to
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Storage {
+    pub device: String,
+    pub path: String,
+    pub free_bytes: u64,
+    pub internal: bool,
+}
+
+struct Mount {
+    device: String,
+    mount_point: String,
+    writable: bool,
+}
+
+impl Storage {
+    pub fn has_space(&self) -> bool {
+        return self.free_bytes >= MIN_FREE_BYTES;
+    }
+
+    /*
+     * Same check for every way of saving, the error is shown to the user
+     */
+    pub fn check_space(&self) -> Result<(), String> {
+        if !self.has_space() {
+            return Err(format!("Storage {} is full", self.path));
+        }
+
+        return Ok(());
+    }
+}
+
+/*
+ * With a configured save directory, that one is used as long as it exists.
+ * Otherwise the first writable removable drive found in /proc/mounts.
+ */
+pub fn find_storage(save_dir: Option<&str>) -> Option<Storage> {
+    if let Some(dir) = save_dir {
+        if !Path::new(dir).is_dir() { return None; }
+
+        return Some(Storage {
+            device: "".to_string(),
+            path: dir.trim_end_matches('/').to_string(),
+            free_bytes: get_free_bytes(dir)?,
+            internal: false,
+        });
+    }
+
+    let mounts = parse_mounts(&fs::read_to_string(MOUNTS_PATH).ok()?);
+    let system_disks = get_system_disks(&mounts);
+
+    return mounts
+        .into_iter()
+        .filter(|m| m.writable && is_removable(m, &system_disks))
+        .find_map(|m| Some(Storage {
+            free_bytes: get_free_bytes(&m.mount_point)?,
+            path: format!("{}/{}", m.mount_point.trim_end_matches('/'), CAPTURE_DIR),
+            device: m.device,
+            internal: false,
+        }));
+}
+
+/*
+ * Like find_storage, but falls back to the internal SD card
+ */
+pub fn find_save_target(save_dir: Option<&str>) -> Option<Storage> {
+    if let Some(storage) = find_storage(save_dir) {
+        return Some(storage);
+    }
+
+    let internal_dir = get_internal_dir();
+    fs::create_dir_all(&internal_dir).ok()?;
+
+    let path = internal_dir.to_string_lossy().to_string();
+
+    return Some(Storage {
+        device: "internal".to_string(),
+        free_bytes: get_free_bytes(&path)?,
+        path,
+        internal: true,
+    });
+}
+
+/*
+ * $XDG_DATA_HOME falls back to ~/.local/share, like the config directory
+ */
+fn get_internal_dir() -> PathBuf {
+    let data_home = env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(PathBuf::from)
+        .or_else(|| env::var("HOME").ok().map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_default();
+
+    return data_home.join(CAPTURE_DIR).join(INTERNAL_DIR);
+}
+
+/*
+ * Moves everything saved internally over to the storage, session folders included.
+ * A capture whose name is taken there already (e.g. by the {seq} counting separately)
+ * is renamed with a counter, together with its sidecars.
+ * Returns how many files were moved.
+ */
+pub fn sync_internal(storage: &Storage) -> io::Result<usize> {
+    if storage.internal { return Ok(0); }
+
+    return move_files(&get_internal_dir(), Path::new(&storage.path));
+}
+
+fn move_files(source_dir: &Path, target_dir: &Path) -> io::Result<usize> {
+    let entries = match fs::read_dir(source_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(0),
+    };
+
+    let mut count = 0;
+
+    // Files of a capture share the stem, e.g. "x.png", "x.csv" and "x.json"
+    let mut captures: Vec<(String, Vec<String>)> = Vec::new();
+
+    for entry in entries.flatten() {
+        let source = entry.path();
+
+        if source.is_dir() {
+            count += move_files(&source, &target_dir.join(entry.file_name()))?;
+
+            // Only succeeds once everything inside was moved
+            fs::remove_dir(&source).unwrap_or_default();
+            continue;
+        }
+
+        let stem = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let extension = source.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+
+        // A recording still being written
+        if extension == bsp_record::PARTIAL_EXTENSION { continue; }
+
+        match captures.iter_mut().find(|(s, _)| *s == stem) {
+            Some((_, extensions)) => extensions.push(extension),
+            None => captures.push((stem, vec![extension])),
+        }
+    }
+
+    for (stem, extensions) in captures {
+        let target_stem = bsp_naming::get_unique_stem(&target_dir.to_string_lossy(), &stem, &extensions);
+
+        if target_stem != stem {
+            eprintln!("{} exists on the storage already, saved as {}", stem, target_stem);
+        }
+
+        // Different filesystems, so copy instead of rename
+        fs::create_dir_all(target_dir)?;
+
+        for extension in extensions {
+            let file_name = |stem: &str| match extension.is_empty() {
+                true => stem.to_string(),
+                false => format!("{}.{}", stem, extension),
+            };
+
+            let source = source_dir.join(file_name(&stem));
+
+            fs::copy(&source, target_dir.join(file_name(&target_stem)))?;
+            fs::remove_file(&source)?;
+
+            count += 1;
+        }
+    }
+
+    return Ok(count);
+}
+
+/*
+ * Each line: <device> <mount point> <type> <options> <dump> <pass>
+ */
+fn parse_mounts(content: &str) -> Vec<Mount> {
+    let mut res = Vec::new();
+
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 { continue; }
+
+        res.push(Mount {
+            device: unescape(fields[0]),
+            mount_point: unescape(fields[1]),
+            writable: fields[3].split(',').any(|o| o == "rw"),
+        });
+    }
+
+    return res;
+}
+
+/*
+ * USB sticks show up as SCSI disks, partitioned (sda1, sdb1, ...) or not (sda).
+ * The Pi may boot from a USB drive as well, so the disks holding the root
+ * and boot partitions are never taken, whatever their other partitions are.
+ */
+fn is_removable(mount: &Mount, system_disks: &[String]) -> bool {
+    if is_system_mount_point(&mount.mount_point) { return false; }
+
+    if let Some(disk) = get_disk(&mount.mount_point) {
+        if system_disks.contains(&disk) { return false; }
+    }
+
+    if mount.device.starts_with("/dev/sd") {
+        return true;
+    }
+
+    return mount.device.starts_with("/dev/")
+        && REMOVABLE_MOUNT_PREFIXES.iter().any(|p| mount.mount_point.starts_with(p));
+}
+
+fn is_system_mount_point(mount_point: &str) -> bool {
+    return mount_point == "/" || mount_point.starts_with("/boot");
+}
+
+/*
+ * Disks (e.g. "sda", "mmcblk0") the system runs from
+ */
+fn get_system_disks(mounts: &[Mount]) -> Vec<String> {
+    return mounts
+        .iter()
+        .filter(|m| is_system_mount_point(&m.mount_point))
+        .filter_map(|m| get_disk(&m.mount_point))
+        .collect();
+}
+
+/*
+ * Disk of the filesystem mounted at the path, found through its device number,
+ * as /proc/mounts may only show "/dev/root" for it
+ */
+fn get_disk(mount_point: &str) -> Option<String> {
+    let dev = fs::metadata(mount_point).ok()?.dev();
+
+    let major = ((dev >> 32) & 0xfffff000) | ((dev >> 8) & 0xfff);
+    let minor = ((dev >> 12) & 0xffffff00) | (dev & 0xff);
+
+    // Links to e.g. .../block/sda/sda1 for a partition, .../block/sda for a whole disk
+    let sys_path = fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+
+    let disk_path = match sys_path.join("partition").exists() {
+        true => sys_path.parent()?.to_path_buf(),
+        false => sys_path,
+    };
+
+    return disk_path.file_name().map(|n| n.to_string_lossy().to_string());
+}
+
+/*
+ * Spaces, tabs, newlines and backslashes are octal escaped, e.g. "\040"
+ */
+fn unescape(field: &str) -> String {
+    let mut res = String::with_capacity(field.len());
+    let mut chars = field.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+
+        let octal: String = (0..3).filter_map(|_| chars.next_if(|d| d.is_digit(8))).collect();
+
+        match u8::from_str_radix(&octal, 8) {
+            Ok(value) if octal.len() == 3 => res.push(value as char),
+            _ => {
+                res.push('\\');
+                res += &octal;
+            },
+        }
+    }
+
+    return res;
+}
+
+fn get_free_bytes(path: &str) -> Option<u64> {
+    let c_path = CString::new(path).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    let result = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if result != 0 { return None; }
+
+    return Some(stat.f_bavail as u64 * stat.f_frsize as u64);
+}
@@ -0,0 +1,616 @@
+use std::{env, fs, path::{Path, PathBuf}};
+use structopt::{clap::ArgMatches, StructOpt};
+
+use super::{bsp, client, convert, mlx, server, stream, timelapse, window, Opt};
+use bsp::bsp_config;
+use bsp::bsp_export::{self, ExportFormats, FrameMetadata};
+
+const EEPROM_WORDS_PER_LINE: usize = 8;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "thermal-camera", about = "Thermal camera based on the MLX90640")]
+pub struct Cli {
+    /// Defaults to $THERMAL_CAMERA_CONFIG or $XDG_CONFIG_HOME/thermal-camera/config.toml
+    // Read by find_config_arg ahead of parsing, only here for validation and --help
+    #[structopt(long, global = true)]
+    #[allow(dead_code)]
+    config: Option<String>,
+
+    /// Settings profile applied on top of the config
+    #[structopt(long, global = true)]
+    profile: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+/*
+ * The settings of a subcommand only override the config where given explicitly,
+ * so their values are taken from the argument matches instead of the groups
+ */
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    #[structopt(about = "Opens the touchscreen interface with the saved options")]
+    Gui {
+        #[structopt(flatten)]
+        _image: ImageArgs,
+
+        #[structopt(flatten)]
+        _measure: MeasureArgs,
+
+        #[structopt(flatten)]
+        _storage: StorageArgs,
+
+        #[structopt(flatten)]
+        _network: NetworkArgs,
+
+        #[structopt(flatten)]
+        _mqtt: MqttArgs,
+    },
+
+    #[structopt(about = "Takes a single picture, the format is chosen by file extension")]
+    Capture {
+        #[structopt(default_value = "out.png")]
+        filename: String,
+
+        /// Changes the sensor framerate before capturing
+        #[structopt(long)]
+        framerate: Option<mlx::Framerates>,
+
+        #[structopt(flatten)]
+        _image: ImageArgs,
+
+        #[structopt(flatten)]
+        _measure: MeasureArgs,
+    },
+
+    #[structopt(about = "Captures at a fixed interval until the count or end time is reached")]
+    Timelapse {
+        /// Time between captures, e.g. "30s", "5m" or "1h"
+        #[structopt(long)]
+        interval: timelapse::Interval,
+
+        /// Stops after this many saved captures
+        #[structopt(long)]
+        count: Option<usize>,
+
+        /// Stops at "HH:MM" (next occurrence) or "YYYY-mm-dd HH:MM"
+        #[structopt(long)]
+        until: Option<timelapse::EndTime>,
+
+        /// Changes the sensor framerate before capturing
+        #[structopt(long)]
+        framerate: Option<mlx::Framerates>,
+
+        #[structopt(flatten)]
+        _image: ImageArgs,
+
+        #[structopt(flatten)]
+        _measure: MeasureArgs,
+
+        #[structopt(flatten)]
+        _storage: StorageArgs,
+    },
+
+    #[structopt(about = "Writes temperature frames to stdout: jsonl, csv or binary (little endian f64 timestamp, f32 ambient, min, max, mean, then the grid)")]
+    Stream {
+        /// jsonl, csv or binary
+        #[structopt(long, default_value = "jsonl")]
+        format: stream::StreamFormats,
+
+        /// Leaves out the 768 pixel temperatures, only ambient, min, max and mean
+        #[structopt(long)]
+        no_grid: bool,
+
+        /// Stops after this many frames, runs until interrupted if not set
+        #[structopt(long)]
+        count: Option<usize>,
+
+        /// Maximum records per second, the sensor framerate is the upper limit
+        #[structopt(long)]
+        max_rate: Option<f32>,
+
+        /// Changes the sensor framerate before streaming
+        #[structopt(long)]
+        framerate: Option<mlx::Framerates>,
+
+        #[structopt(flatten)]
+        _measure: MeasureArgs,
+    },
+
+    #[structopt(about = "Runs HTTP server (--http) and/or MQTT client (--mqtt) without GUI")]
+    Serve {
+        /// Changes the sensor framerate before serving
+        #[structopt(long)]
+        framerate: Option<mlx::Framerates>,
+
+        #[structopt(flatten)]
+        _image: ImageArgs,
+
+        #[structopt(flatten)]
+        _measure: MeasureArgs,
+
+        #[structopt(flatten)]
+        _storage: StorageArgs,
+
+        #[structopt(flatten)]
+        _network: NetworkArgs,
+
+        #[structopt(flatten)]
+        _mqtt: MqttArgs,
+    },
+
+    #[structopt(about = "Prints the frames of a camera running with --tcp as JSON Lines, with the token of network.http_token. A gap in seq means frames were skipped")]
+    Receive {
+        /// "host:port" of the camera, the port defaults to 8081
+        address: String,
+
+        /// Stops after this many frames, runs until interrupted if not set
+        #[structopt(long)]
+        count: Option<usize>,
+    },
+
+    #[structopt(about = "Prints serial, calibration summary and settings of the sensor")]
+    Info,
+
+    #[structopt(about = "Prints the raw EEPROM calibration data as hex")]
+    DumpEeprom {
+        /// Writes the words big endian into this file instead
+        #[structopt(long)]
+        output: Option<String>,
+    },
+
+    #[structopt(about = "Changes sensor settings, they are kept until power off")]
+    Set {
+        #[structopt(long)]
+        framerate: Option<mlx::Framerates>,
+
+        /// ADC resolution in bits, 16 to 19
+        #[structopt(long)]
+        resolution: Option<mlx::Resolutions>,
+    },
+
+    #[structopt(about = "Re-renders all radiometric captures of a directory, no sensor needed")]
+    Convert {
+        input_dir: String,
+
+        /// Defaults to "<input_dir>/converted"
+        #[structopt(long)]
+        output_dir: Option<String>,
+
+        /// png, tiff, csv, npy or annotated
+        #[structopt(long = "save-format", name = "save-format")]
+        _save_format: Option<ExportFormats>,
+
+        #[structopt(flatten)]
+        _image: ImageArgs,
+
+        #[structopt(flatten)]
+        _measure: MeasureArgs,
+    },
+
+    #[structopt(about = "Manages settings profiles, select one with --profile")]
+    Profile(ProfileCommand),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ProfileCommand {
+    #[structopt(about = "Lists the saved profiles, the active one is marked")]
+    List,
+
+    #[structopt(about = "Saves the current settings as profile")]
+    Save {
+        name: String,
+    },
+
+    #[structopt(about = "Renames a profile")]
+    Rename {
+        name: String,
+        new_name: String,
+    },
+
+    #[structopt(about = "Deletes a profile")]
+    Delete {
+        name: String,
+    },
+
+    #[structopt(about = "Copies a profile into a directory, e.g. to use it on another device")]
+    Export {
+        name: String,
+
+        #[structopt(default_value = ".")]
+        target_dir: String,
+    },
+
+    #[structopt(about = "Adds an exported profile, named after the file")]
+    Import {
+        file: String,
+    },
+}
+
+/*
+ * Settings groups of the subcommands, each subcommand only takes the ones it uses.
+ * The values are parsed here for validation and --help, apply_args takes them
+ * from the matches, so the field names have to be the arguments in bsp_config::KEYS.
+ */
+#[derive(Debug, StructOpt)]
+#[allow(dead_code)]
+pub struct ImageArgs {
+    /// hue, gray, cheap or the name of a custom palette
+    #[structopt(long = "color")]
+    color_type: Option<mlx::ColorTypes>,
+
+    /// auto, locked or manual (--range-min to --range-max)
+    #[structopt(long)]
+    range_mode: Option<mlx::RangeModes>,
+
+    /// Lower end of the manual range
+    #[structopt(long)]
+    range_min: Option<f32>,
+
+    /// Upper end of the manual range
+    #[structopt(long)]
+    range_max: Option<f32>,
+
+    /// off, black/white or white/black, marks temperatures outside the manual range
+    #[structopt(long)]
+    saturation: Option<mlx::SaturationColors>,
+
+    /// linear, percentile or histogram
+    #[structopt(long)]
+    gain_mode: Option<mlx::GainModes>,
+
+    /// Percentile mapped to the lowest color
+    #[structopt(long)]
+    percentile_low: Option<f32>,
+
+    /// Percentile mapped to the highest color
+    #[structopt(long)]
+    percentile_high: Option<f32>,
+
+    /// off, band, above or below, limited by --isotherm-min and --isotherm-max
+    #[structopt(long)]
+    isotherm_mode: Option<mlx::IsothermModes>,
+
+    /// Lower limit of the isotherm
+    #[structopt(long)]
+    isotherm_min: Option<f32>,
+
+    /// Upper limit of the isotherm
+    #[structopt(long)]
+    isotherm_max: Option<f32>,
+
+    /// green, magenta or cyan
+    #[structopt(long)]
+    isotherm_color: Option<mlx::IsothermColors>,
+
+    /// Shows everything outside the isotherm in gray
+    #[structopt(long)]
+    isotherm_gray: bool,
+
+    /// nearest, bilinear, bicubic or lanczos, used when scaling up
+    #[structopt(long)]
+    interpolation: Option<mlx::Interpolations>,
+
+    /// Saved images are this many times the 32 x 24 of the sensor
+    #[structopt(long)]
+    scale_factor: Option<usize>,
+}
+
+#[derive(Debug, StructOpt)]
+#[allow(dead_code)]
+pub struct MeasureArgs {
+    /// 0.1 to 1.0, e.g. 0.95 for most painted or organic surfaces
+    #[structopt(long)]
+    emissivity: Option<f32>,
+
+    /// celsius, fahrenheit or kelvin
+    #[structopt(long)]
+    unit: Option<mlx::TemperatureUnits>,
+}
+
+#[derive(Debug, StructOpt)]
+#[allow(dead_code)]
+pub struct StorageArgs {
+    /// Directory captures are saved to, detects removable storage if not set
+    #[structopt(long)]
+    save_dir: Option<String>,
+
+    /// png, tiff, csv, npy or annotated
+    #[structopt(long)]
+    save_format: Option<ExportFormats>,
+
+    /// Name of saved captures, tokens: {date} {time} {seq} {job} {session} {max}
+    #[structopt(long)]
+    filename_template: Option<String>,
+
+    /// Job or site name, used by the {job} token
+    #[structopt(long)]
+    job: Option<String>,
+
+    /// Captures of a session go into a folder of the same name
+    #[structopt(long)]
+    session: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+#[allow(dead_code)]
+pub struct NetworkArgs {
+    /// Serves live view and temperatures over HTTP, needs network.http_token of the config
+    /// or $THERMAL_CAMERA_NETWORK_HTTP_TOKEN(_FILE)
+    #[structopt(long = "http")]
+    http_enabled: bool,
+
+    /// Port of the HTTP server, 8080 if not set
+    #[structopt(long)]
+    http_port: Option<u16>,
+
+    /// Raw temperatures over TCP for other programs, clients slower than the sensor skip frames
+    #[structopt(long = "tcp")]
+    tcp_enabled: bool,
+
+    /// Port of the TCP stream, 8081 if not set
+    #[structopt(long)]
+    tcp_port: Option<u16>,
+}
+
+#[derive(Debug, StructOpt)]
+#[allow(dead_code)]
+pub struct MqttArgs {
+    /// Publishes temperatures to an MQTT broker
+    #[structopt(long = "mqtt")]
+    mqtt_enabled: bool,
+
+    /// Broker address, localhost if not set
+    #[structopt(long)]
+    mqtt_host: Option<String>,
+
+    /// Broker port, 1883 if not set
+    #[structopt(long)]
+    mqtt_port: Option<u16>,
+
+    /// Anonymous if not set, the password is mqtt.password of the config or $THERMAL_CAMERA_MQTT_PASSWORD(_FILE)
+    #[structopt(long)]
+    mqtt_username: Option<String>,
+
+    /// Base of all topics, e.g. "thermal-camera/state"
+    #[structopt(long)]
+    mqtt_topic: Option<String>,
+
+    /// Seconds between two state messages
+    #[structopt(long)]
+    mqtt_interval: Option<u32>,
+
+    /// Home Assistant discovery, disabled if empty
+    #[structopt(long)]
+    mqtt_discovery_prefix: Option<String>,
+
+    /// Publishes an alarm while the min temperature is below
+    #[structopt(long)]
+    mqtt_alarm_min: Option<f32>,
+
+    /// Publishes an alarm while the max temperature is above
+    #[structopt(long)]
+    mqtt_alarm_max: Option<f32>,
+}
+
+/*
+ * Settings are merged from the config file, environment variables and
+ * the explicitly given arguments, in that order
+ */
+pub fn run() {
+    let config_path = bsp_config::get_config_path(find_config_arg().as_deref());
+
+    // Custom palettes must be known before parsing color types
+    mlx::load_palettes(bsp::read_palette_files(&bsp_config::get_palette_dir(&config_path)));
+
+    let matches = Cli::clap().get_matches();
+    let cli = Cli::from_clap(&matches);
+
+    let mut settings = bsp_config::load(&config_path, cli.profile.as_deref());
+
+    if let (_, Some(args)) = matches.subcommand() {
+        apply_args(&mut settings, args);
+    }
+
+    execute(cli.command, settings, config_path);
+}
+
+/*
+ * --config ahead of parsing, as the palettes next to the config are needed for it
+ */
+fn find_config_arg() -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--" { break; }
+
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.to_string());
+        }
+
+        if arg == "--config" {
+            return args.get(i + 1).cloned();
+        }
+    }
+
+    return None;
+}
+
+fn apply_args(settings: &mut Opt, args: &ArgMatches) {
+    for (key, arg) in bsp_config::KEYS.iter() {
+        if arg.is_empty() || args.occurrences_of(arg) == 0 { continue; }
+
+        // Flags have no value
+        let val = args.value_of(arg).unwrap_or("true");
+
+        settings.set_value(key, val).unwrap_or_else(|err| eprintln!("{}", err));
+    }
+}
+
+/*
+ * Errors go to stderr with a non-zero exit code, so scripts can react to them
+ */
+fn execute(command: Command, mut settings: Opt, config_path: PathBuf) {
+    mlx::set_emissivity(settings.emissivity);
+
+    let res = match command {
+        Command::Gui { .. } => {
+            window::open_window(settings, config_path);
+            Ok(())
+        },
+
+        Command::Capture { filename, framerate, .. } => {
+            apply_framerate(&mut settings, framerate);
+            capture(&settings, &filename)
+        },
+
+        Command::Timelapse { interval, count, until, framerate, .. } => {
+            apply_framerate(&mut settings, framerate);
+            timelapse::run(&settings, interval, count, until).map(|_| ())
+        },
+
+        Command::Stream { format, no_grid, count, max_rate, framerate, .. } => {
+            apply_framerate(&mut settings, framerate);
+            stream::run(&settings, format, !no_grid, count, max_rate)
+        },
+
+        Command::Serve { framerate, .. } => {
+            apply_framerate(&mut settings, framerate);
+            server::run(&settings, &config_path)
+        },
+
+        Command::Receive { address, count } => {
+            client::run(&address, &settings.http_token, count)
+        },
+
+        Command::Info => info(),
+
+        Command::DumpEeprom { output } => dump_eeprom(output.as_deref()),
+
+        Command::Set { framerate, resolution } => set(framerate, resolution),
+
+        Command::Profile(command) => profile(command, &settings, &config_path),
+
+        Command::Convert { input_dir, output_dir, .. } => {
+            let output_dir = output_dir.unwrap_or(format!("{}/converted", input_dir));
+
+            convert::convert_dir(&settings, &input_dir, &output_dir).map(|count| {
+                println!("Converted {} captures into {}", count, output_dir);
+            })
+        },
+    };
+
+    if let Err(err) = res {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn profile(command: ProfileCommand, settings: &Opt, config_path: &Path) -> Result<(), String> {
+    match command {
+        ProfileCommand::List => {
+            for name in bsp_config::list_profiles(config_path) {
+                let active = settings.profile.as_deref() == Some(name.as_str());
+                println!("{} {}", if active { "*" } else { " " }, name);
+            }
+        },
+
+        ProfileCommand::Save { name } => {
+            let name = bsp_config::save_profile(config_path, &name, settings).map_err(|err| err.to_string())?;
+            println!("Saved profile {}", name);
+        },
+
+        ProfileCommand::Rename { name, new_name } => {
+            let new_name = bsp_config::rename_profile(config_path, &name, &new_name).map_err(|err| err.to_string())?;
+            println!("Renamed profile {} to {}", name, new_name);
+        },
+
+        ProfileCommand::Delete { name } => {
+            bsp_config::delete_profile(config_path, &name).map_err(|err| format!("Failed to delete {}: {}", name, err))?;
+            println!("Deleted profile {}", name);
+        },
+
+        ProfileCommand::Export { name, target_dir } => {
+            let path = bsp_config::export_profile(config_path, &name, Path::new(&target_dir))
+                .map_err(|err| format!("Failed to export {}: {}", name, err))?;
+            println!("Exported profile {} to {}", name, path.display());
+        },
+
+        ProfileCommand::Import { file } => {
+            let name = bsp_config::import_profile(config_path, Path::new(&file))
+                .map_err(|err| format!("Failed to import {}: {}", file, err))?;
+            println!("Imported profile {}", name);
+        },
+    }
+
+    return Ok(());
+}
+
+fn apply_framerate(opt: &mut Opt, framerate: Option<mlx::Framerates>) {
+    if let Some(framerate) = framerate {
+        mlx::set_framerate(framerate);
+        opt.framerate = framerate;
+    }
+    else if let Ok(framerate) = mlx::read_framerate() {
+        opt.framerate = framerate;
+    }
+}
+
+fn capture(opt: &Opt, file_path: &str) -> Result<(), String> {
+    let format = ExportFormats::from_path(file_path)?;
+    let image = mlx::take_image(&opt.color_settings(), opt.unit)?;
+    let metadata = FrameMetadata::new(opt, &image, opt.unit);
+
+    return bsp_export::export(file_path, format, &image, &metadata)
+        .map_err(|err| format!("Failed to write {}: {}", file_path, err));
+}
+
+fn info() -> Result<(), String> {
+    for (name, value) in mlx::get_sensor_info()?.summary() {
+        println!("{:<16}{}", format!("{}:", name), value);
+    }
+
+    println!("{:<16}{} Hz", "Framerate:", mlx::read_framerate()?.to_string());
+    println!("{:<16}{} bit", "Resolution:", mlx::read_resolution()?.to_string());
+    println!("{:<16}{}x{}", "Pixels:", mlx::PIXELS_WIDTH, mlx::PIXELS_HEIGHT);
+    println!("{:<16}{:.2}", "Emissivity:", mlx::get_emissivity());
+
+    return Ok(());
+}
+
+fn dump_eeprom(output: Option<&str>) -> Result<(), String> {
+    let eeprom = mlx::read_eeprom()?;
+
+    if let Some(file_path) = output {
+        let buf: Vec<u8> = eeprom.iter().flat_map(|w| w.to_be_bytes()).collect();
+        return fs::write(file_path, buf).map_err(|err| format!("Failed to write {}: {}", file_path, err));
+    }
+
+    for (i, line) in eeprom.chunks(EEPROM_WORDS_PER_LINE).enumerate() {
+        let words: Vec<String> = line.iter().map(|w| format!("{:04X}", w)).collect();
+        println!("0x{:04X}: {}", mlx::ADDRESS_EEPROM as usize + i * EEPROM_WORDS_PER_LINE, words.join(" "));
+    }
+
+    return Ok(());
+}
+
+fn set(framerate: Option<mlx::Framerates>, resolution: Option<mlx::Resolutions>) -> Result<(), String> {
+    if framerate.is_none() && resolution.is_none() {
+        return Err("Nothing to set, use --framerate and/or --resolution".to_string());
+    }
+
+    if let Some(framerate) = framerate {
+        mlx::set_framerate(framerate);
+    }
+
+    if let Some(resolution) = resolution {
+        mlx::set_resolution(resolution);
+    }
+
+    // Read back, so failed writes show up
+    println!("{:<16}{} Hz", "Framerate:", mlx::read_framerate()?.to_string());
+    println!("{:<16}{} bit", "Resolution:", mlx::read_resolution()?.to_string());
+
+    return Ok(());
+}
@@ -0,0 +1,374 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+use serde::Serialize;
+
+use super::mlx;
+use super::server::server_tcp::{self, HEADER_LEN, MAGIC, MAX_TOKEN_LEN, STATUS_BUSY, STATUS_OK, VERSION};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+// The camera sends the last frame again at least every 5 seconds
+const READ_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Far above the 32 x 24 of the sensor, anything larger is a broken stream
+const MAX_PIXELS: usize = 1 << 20;
+
+/*
+ * One frame of the TCP stream of server_tcp
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteFrame {
+    pub seq: u32,
+    pub timestamp: f64,
+    #[serde(serialize_with = "serialize_unit")]
+    pub unit: mlx::TemperatureUnits,
+    pub width: usize,
+    pub height: usize,
+    pub min_temp: f32,
+    pub max_temp: f32,
+    pub mean_temp: f32,
+    pub temperature_grid: Vec<f32>,
+}
+
+/*
+ * Reads frames from a camera running with --tcp. As iterator it reconnects
+ * whenever the connection is lost and only ends on a wrong token or an incompatible camera,
+ * the reason is kept for take_error.
+ */
+pub struct FrameClient {
+    address: String,
+    token: String,
+    stream: Option<BufReader<TcpStream>>,
+    error: Option<io::Error>,
+}
+
+fn serialize_unit<S: serde::Serializer>(unit: &mlx::TemperatureUnits, serializer: S) -> Result<S::Ok, S::Error> {
+    return serializer.serialize_str(unit.symbol());
+}
+
+impl FrameClient {
+    /*
+     * address as "host:port", nothing happens until the first frame is asked for
+     */
+    pub fn new(address: &str, token: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            token: token.to_string(),
+            stream: None,
+            error: None,
+        }
+    }
+
+    /*
+     * Why the iterator ended
+     */
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        return self.error.take();
+    }
+
+    /*
+     * Blocks until the next frame, connects first if needed. After an error
+     * the connection is dropped and the next call connects again.
+     */
+    pub fn read_frame(&mut self) -> io::Result<RemoteFrame> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connect()?);
+        }
+
+        let res = read_frame(self.stream.as_mut().unwrap());
+
+        if res.is_err() {
+            self.stream = None;
+        }
+
+        return res;
+    }
+
+    fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        if self.token.len() > MAX_TOKEN_LEN || self.token.contains('\n') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid token"));
+        }
+
+        let addr = self.address.to_socket_addrs()?
+            .next()
+            .ok_or(io::Error::new(io::ErrorKind::NotFound, format!("Unknown address {}", self.address)))?;
+
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        writeln!(stream, "{}", self.token)?;
+
+        let mut reader = BufReader::new(stream);
+
+        let mut reply = [0u8; 6];
+        reader.read_exact(&mut reply)?;
+
+        check_version(&reply[0..4], reply[4])?;
+
+        match reply[5] {
+            STATUS_OK => (),
+
+            // Tried again later
+            STATUS_BUSY => return Err(io::Error::new(io::ErrorKind::Other, "Too many clients")),
+
+            _ => return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Wrong token")),
+        }
+
+        return Ok(reader);
+    }
+}
+
+impl Iterator for FrameClient {
+    type Item = RemoteFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() { return None; }
+
+        loop {
+            match self.read_frame() {
+                Ok(frame) => return Some(frame),
+
+                Err(err) if is_permanent(&err) => {
+                    self.error = Some(err);
+                    return None;
+                },
+
+                Err(err) => {
+                    eprintln!("Frame stream from {} lost, reconnecting: {}", self.address, err);
+                    thread::sleep(RECONNECT_DELAY);
+                },
+            }
+        }
+    }
+}
+
+/*
+ * Writes the frames to stdout as JSON Lines until the count is reached or the reader goes away
+ */
+pub fn run(address: &str, token: &str, count: Option<usize>) -> Result<(), String> {
+    let address = match address.contains(':') {
+        true => address.to_string(),
+        false => format!("{}:{}", address, server_tcp::DEFAULT_PORT),
+    };
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    let mut frames = FrameClient::new(&address, token);
+
+    for frame in frames.by_ref().take(count.unwrap_or(usize::MAX)) {
+        let line = serde_json::to_string(&frame).map_err(|err| err.to_string())?;
+
+        match writeln!(out, "{}", line).and_then(|_| out.flush()) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(err) => return Err(format!("Failed to write to stdout: {}", err)),
+        }
+    }
+
+    if let Some(err) = frames.take_error() {
+        return Err(format!("Frame stream from {} stopped: {}", address, err));
+    }
+
+    return Ok(());
+}
+
+/*
+ * Trying again won't help with these
+ */
+fn is_permanent(err: &io::Error) -> bool {
+    return matches!(err.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported);
+}
+
+fn check_version(magic: &[u8], version: u8) -> io::Result<()> {
+    if magic != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Not a thermal camera frame stream"));
+    }
+
+    if version != VERSION {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, format!("Unsupported stream version {}, expected {}", version, VERSION)));
+    }
+
+    return Ok(());
+}
+
+fn read_frame(reader: &mut impl Read) -> io::Result<RemoteFrame> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    check_version(&header[0..4], header[4])?;
+
+    let unit = server_tcp::unit_from_code(header[5])
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData, format!("Unknown unit {}", header[5])))?;
+
+    let header_len = u16::from_le_bytes([header[6], header[7]]) as usize;
+
+    if header_len < HEADER_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Header of {} bytes too short", header_len)));
+    }
+
+    // Fields appended by newer cameras
+    io::copy(&mut reader.by_ref().take((header_len - HEADER_LEN) as u64), &mut io::sink())?;
+
+    let width = u16::from_le_bytes([header[8], header[9]]) as usize;
+    let height = u16::from_le_bytes([header[10], header[11]]) as usize;
+
+    if width * height > MAX_PIXELS {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Frame of {} x {} too large", width, height)));
+    }
+
+    let f32_at = |i: usize| f32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+
+    let mut grid = vec![0u8; width * height * 4];
+    reader.read_exact(&mut grid)?;
+
+    return Ok(RemoteFrame {
+        seq: u32::from_le_bytes(header[12..16].try_into().unwrap()),
+        timestamp: f64::from_le_bytes(header[16..24].try_into().unwrap()),
+        unit,
+        width,
+        height,
+        min_temp: f32_at(24),
+        max_temp: f32_at(28),
+        mean_temp: f32_at(32),
+        temperature_grid: grid.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use super::super::{bsp::bsp_export::FrameMetadata, live};
+
+    fn test_frame(seq: u64) -> live::Frame {
+        let temperature_grid: Vec<f32> = (0..12).map(|i| 20.0 + i as f32 * 0.5).collect();
+
+        live::Frame {
+            seq,
+            metadata: FrameMetadata {
+                timestamp: "2024-05-01T12:00:00.250+00:00".to_string(),
+                width: 4,
+                height: 3,
+                unit: mlx::TemperatureUnits::Fahrenheit.symbol().to_string(),
+                min_temp: 20.0,
+                max_temp: 25.5,
+                color_type: "Hue".to_string(),
+                color_range: (20.0, 25.5),
+                framerate: "2".to_string(),
+                emissivity: 1.0,
+                serial: "test".to_string(),
+                markers: Vec::new(),
+            },
+            mean_temp: 22.75,
+            temperature_grid,
+            width: 4,
+            height: 3,
+            pixels: Vec::new(),
+        }
+    }
+
+    fn assert_frame(res: &RemoteFrame, frame: &live::Frame) {
+        assert_eq!(res.seq as u64, frame.seq);
+        assert_eq!(res.timestamp, 1714564800.25);
+        assert_eq!(res.unit, mlx::TemperatureUnits::Fahrenheit);
+        assert_eq!((res.width, res.height), (4, 3));
+        assert_eq!(res.min_temp, frame.metadata.min_temp);
+        assert_eq!(res.max_temp, frame.metadata.max_temp);
+        assert_eq!(res.mean_temp, frame.mean_temp);
+        assert_eq!(res.temperature_grid, frame.temperature_grid);
+    }
+
+    #[test]
+    fn round_trip() {
+        let frame = test_frame(7);
+
+        let mut buf = Vec::new();
+        server_tcp::write_frame(&mut buf, &frame).unwrap();
+
+        assert_eq!(buf.len(), HEADER_LEN + 12 * 4);
+
+        let res = read_frame(&mut buf.as_slice()).unwrap();
+        assert_frame(&res, &frame);
+    }
+
+    #[test]
+    fn skips_extended_header() {
+        let (first, second) = (test_frame(1), test_frame(2));
+        let extra = [0xAB; 6];
+
+        let mut buf = Vec::new();
+        server_tcp::write_frame(&mut buf, &first).unwrap();
+
+        // As a newer camera would send it, with fields appended to the header
+        buf[6..8].copy_from_slice(&((HEADER_LEN + extra.len()) as u16).to_le_bytes());
+        buf.splice(HEADER_LEN..HEADER_LEN, extra);
+
+        server_tcp::write_frame(&mut buf, &second).unwrap();
+
+        let mut reader = buf.as_slice();
+        assert_frame(&read_frame(&mut reader).unwrap(), &first);
+        assert_frame(&read_frame(&mut reader).unwrap(), &second);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn rejects_broken_header() {
+        let mut buf = Vec::new();
+        server_tcp::write_frame(&mut buf, &test_frame(0)).unwrap();
+
+        let mut short = buf.clone();
+        short[6..8].copy_from_slice(&((HEADER_LEN - 1) as u16).to_le_bytes());
+        assert_eq!(read_frame(&mut short.as_slice()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut newer = buf.clone();
+        newer[4] = VERSION + 1;
+        assert_eq!(read_frame(&mut newer.as_slice()).unwrap_err().kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn reconnects_after_lost_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        // Drops the first connection after one frame, the second one stays
+        let server = thread::spawn(move || {
+            let mut tokens = Vec::new();
+            let mut streams = Vec::new();
+
+            for seq in [1, 2] {
+                let (stream, _) = listener.accept().unwrap();
+
+                let mut token = String::new();
+                BufReader::new(stream.try_clone().unwrap()).read_line(&mut token).unwrap();
+                tokens.push(token);
+
+                let mut out = BufWriter::new(stream);
+                out.write_all(MAGIC).unwrap();
+                out.write_all(&[VERSION, STATUS_OK]).unwrap();
+                server_tcp::write_frame(&mut out, &test_frame(seq)).unwrap();
+                out.flush().unwrap();
+
+                if seq == 2 {
+                    streams.push(out);
+                }
+            }
+
+            return (tokens, streams);
+        });
+
+        let mut frames = FrameClient::new(&address, "secret");
+
+        assert_frame(&frames.next().unwrap(), &test_frame(1));
+        assert_frame(&frames.next().unwrap(), &test_frame(2));
+        assert!(frames.take_error().is_none());
+
+        let (tokens, _streams) = server.join().unwrap();
+        assert_eq!(tokens, vec!["secret\n".to_string(); 2]);
+    }
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use super::{bsp, mlx, Opt};
+use bsp::bsp_export;
+
+/*
+ * Re-renders every radiometric capture inside the input directory with
+ * the coloring, unit and save format of the options. No sensor needed.
+ * Returns how many captures were converted.
+ */
+pub fn convert_dir(opt: &Opt, input_dir: &str, output_dir: &str) -> Result<usize, String> {
+    let files = bsp::list_radiometric_files(input_dir)
+        .map_err(|err| format!("Failed to read {}: {}", input_dir, err))?;
+
+    let mut count = 0;
+
+    for file_path in files.iter() {
+        let output_path = format!(
+            "{}/{}.{}",
+            output_dir,
+            bsp::get_capture_stem(file_path),
+            opt.save_format.extension()
+        );
+
+        match convert_file(opt, file_path, &output_path) {
+            Ok(()) => {
+                println!("{} -> {}", file_path, output_path);
+                count += 1;
+            },
+
+            Err(err) => println!("Skipping {}: {}", file_path, err),
+        }
+    }
+
+    return Ok(count);
+}
+
+fn convert_file(opt: &Opt, file_path: &str, output_path: &str) -> Result<(), String> {
+    // Don't overwrite the input when converting in place
+    if Path::new(file_path) == Path::new(output_path) {
+        return Err("Output would overwrite the input".to_string());
+    }
+
+    let (mut temperatures, mut metadata) = bsp::read_capture_temperatures(file_path)?;
+    temperatures.convert_unit(mlx::TemperatureUnits::Celsius, opt.unit);
+
+    let image = mlx::mlx_image::color_image(&opt.color_settings(), &temperatures);
+
+    // Keep when and with what the capture was taken, update how it is shown
+    metadata.unit = opt.unit.symbol().to_string();
+    metadata.min_temp = image.temperature_read.min_temp;
+    metadata.max_temp = image.temperature_read.max_temp;
+    metadata.color_type = opt.color_type.to_string();
+    metadata.color_range = image.color_range;
+
+    for marker in metadata.markers.iter_mut() {
+        marker.temperature = image.scaled.get_temperature(marker.x, marker.y);
+    }
+
+    return bsp_export::export(output_path, opt.save_format, &image, &metadata).map_err(|err| err.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use bsp_export::{ExportFormats, FrameMetadata};
+
+    #[test]
+    fn converts_every_capture_format() {
+        let dir = env::temp_dir().join(format!("thermal-camera-convert-{}", std::process::id()));
+        let (input_dir, output_dir) = (dir.join("input"), dir.join("output"));
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+        fs::create_dir_all(&input_dir).unwrap();
+
+        let mut opt = Opt::default();
+        let mut temperatures = mlx::TemperatureRead::default();
+
+        for (i, temp) in temperatures.temperature_grid.iter_mut().enumerate() {
+            *temp = 20.0 + (i % 64) as f32 * 0.25;
+        }
+
+        let image = mlx::mlx_image::color_image(&opt.color_settings(), &temperatures);
+        let metadata = FrameMetadata::new(&opt, &image, mlx::TemperatureUnits::Celsius);
+
+        let formats = [ExportFormats::Png, ExportFormats::Tiff, ExportFormats::Csv, ExportFormats::Npy, ExportFormats::Annotated];
+
+        for format in formats {
+            let path = input_dir.join(format!("{}.{}", format.to_string(), format.extension()));
+            bsp_export::export(&path.to_string_lossy(), format, &image, &metadata).unwrap();
+        }
+
+        opt.save_format = ExportFormats::Npy;
+
+        let count = convert_dir(&opt, &input_dir.to_string_lossy(), &output_dir.to_string_lossy()).unwrap();
+        assert_eq!(count, formats.len());
+
+        for format in formats {
+            let path = output_dir.join(format!("{}.npy", format.to_string()));
+            let (converted, _) = bsp::read_capture_temperatures(&path.to_string_lossy()).unwrap();
+
+            for (a, b) in converted.temperature_grid.iter().zip(temperatures.temperature_grid.iter()) {
+                assert!((a - b).abs() < 0.01, "{}: {} != {}", format.to_string(), a, b);
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap_or_default();
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use lazy_static::lazy_static;
+
+use super::{bsp, mlx, Opt};
+use bsp::bsp_export::{FrameMetadata, Marker};
+
+// Name of the marker set by tapping the image
+pub const SPOT_MARKER: &str = "Spot";
+
+/*
+ * Newest frame, shared with the HTTP server and the MQTT client
+ */
+pub struct Frame {
+    pub seq: u64,
+    pub metadata: FrameMetadata,
+    pub mean_temp: f32,
+    pub temperature_grid: Vec<f32>,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+lazy_static! {
+    static ref LATEST_FRAME: Mutex<Option<Arc<Frame>>> = Mutex::new(None);
+    static ref FRAME_READY: Condvar = Condvar::new();
+    // Position inside the image, 0 to 1 on both axes
+    static ref SPOT: Mutex<Option<(f32, f32)>> = Mutex::new(None);
+}
+
+// Copying frames is skipped until a service needs them
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/*
+ * Makes the image available to the services, does nothing while none runs
+ */
+pub fn publish(opt: &Opt, image: &mlx::ImageRead, unit: mlx::TemperatureUnits) {
+    if !ENABLED.load(Ordering::SeqCst) { return; }
+
+    let temperatures = &image.temperature_read.temperature_grid;
+
+    // Built before locking, the metadata may read from the sensor
+    let mut metadata = FrameMetadata::new(opt, image, unit);
+    metadata.markers.extend(get_spot_marker(image));
+
+    let mut frame = Frame {
+        seq: 0,
+        metadata,
+        mean_temp: temperatures.iter().sum::<f32>() / mlx::PIXEL_COUNT as f32,
+        temperature_grid: temperatures.to_vec(),
+        width: image.scaled.width,
+        height: image.scaled.height,
+        pixels: image.scaled.pixels.clone(),
+    };
+
+    let mut latest = LATEST_FRAME.lock().unwrap();
+    frame.seq = latest.as_ref().map(|f| f.seq + 1).unwrap_or(0);
+
+    latest.replace(Arc::new(frame));
+
+    FRAME_READY.notify_all();
+}
+
+/*
+ * The spot is measured on every frame from then on, None removes it
+ */
+pub fn set_spot(spot: Option<(f32, f32)>) {
+    *SPOT.lock().unwrap() = spot;
+}
+
+pub fn get_spot_marker(image: &mlx::ImageRead) -> Option<Marker> {
+    let (u, v) = (*SPOT.lock().unwrap())?;
+
+    return Some(Marker {
+        name: SPOT_MARKER.to_string(),
+        x: u,
+        y: v,
+        temperature: image.scaled.get_temperature(u, v),
+    });
+}
+
+pub fn get_latest_frame() -> Option<Arc<Frame>> {
+    return LATEST_FRAME.lock().unwrap().clone();
+}
+
+/*
+ * Blocks until a frame newer than seq arrives, None after the timeout
+ */
+pub fn wait_for_frame(seq: Option<u64>, timeout: Duration) -> Option<Arc<Frame>> {
+    let latest = LATEST_FRAME.lock().unwrap();
+
+    let (latest, _) = FRAME_READY.wait_timeout_while(latest, timeout, |frame| {
+        match (frame, seq) {
+            (Some(frame), Some(seq)) => frame.seq <= seq,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }).unwrap();
+
+    return latest.clone().filter(|frame| Some(frame.seq) != seq);
+}
@@ -1,9 +1,15 @@
 use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
 
 mod bsp_mlx;
 pub mod mlx_image;
+pub mod mlx_interpolate;
+pub mod mlx_palette;
 
-use bsp_mlx::{REGISTER_STATUS, REGISTER_CTRL, ADDRESS_RAM};
+use bsp_mlx::{REGISTER_STATUS, REGISTER_CTRL, ADDRESS_RAM, ADDRESS_DEVICE_ID, ADDRESS_DEVICE_OPTIONS};
+pub use bsp_mlx::ADDRESS_EEPROM;
 
 pub const PIXELS_WIDTH: usize = 32;
 pub const PIXELS_HEIGHT: usize = 24;
@@ -13,6 +19,28 @@ pub const GRADIENT_WIDTH: usize = 1;
 pub const GRADIENT_HEIGHT: usize = 256;
 pub const GRADIENT_COUNT: usize = GRADIENT_WIDTH * GRADIENT_HEIGHT;
 
+pub const SCALE_TICK_COUNT: usize = 5;
+
+pub const MAX_SCALE_FACTOR: usize = 16;
+
+// Custom palettes can't take these names
+pub const BUILTIN_COLOR_TYPES: [ColorTypes; 3] = [ColorTypes::Gray, ColorTypes::Cheap, ColorTypes::Hue];
+
+// A failed serial read is tried again after this
+const SERIAL_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+// Emissivity of the measured surface, 1.0 is a perfect black body
+pub const DEFAULT_EMISSIVITY: f32 = 1.0;
+pub const MIN_EMISSIVITY: f32 = 0.1;
+
+// Field of view variant in the device options word at 0x240A,
+// other values are reported as unknown
+const FOV_MASK: u16 = 0x0003;
+const FOV_VARIANTS: [(u16, &str); 2] = [
+    (0x0001, "BAA 110 x 75°"),
+    (0x0002, "BAB 55 x 35°"),
+];
+
 #[derive(Debug, Clone, Copy)]
 pub enum Framerates {
     Half = 0b000,
@@ -25,11 +53,136 @@ pub enum Framerates {
     Sixtyfour = 0b111,
 }
 
+// ADC resolution in bits
+#[derive(Debug, Clone, Copy)]
+pub enum Resolutions {
+    Sixteen = 0b00,
+    Seventeen = 0b01,
+    Eighteen = 0b10,
+    Nineteen = 0b11,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ColorTypes {
     Gray,
     Cheap,
     Hue,
+    Custom(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeModes {
+    Auto,
+    Locked,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GainModes {
+    Linear,
+    Percentile,
+    Equalized,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IsothermModes {
+    Off,
+    Band,
+    Above,
+    Below,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IsothermColors {
+    Green,
+    Magenta,
+    Cyan,
+}
+
+/*
+ * Marks temperatures inside the range, above the lower
+ * or below the upper bound in a distinct color
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Isotherm {
+    pub mode: IsothermModes,
+    pub range: (f32, f32),
+    pub color: IsothermColors,
+    pub gray_background: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolations {
+    Nearest,
+    Bilinear,
+    Bicubic,
+    Lanczos,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaturationColors {
+    Off,
+    BlackWhite,
+    WhiteBlack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureUnits {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+/*
+ * Everything needed to turn temperatures into colors.
+ * A range of None stretches the colors between min and max of each frame,
+ * or between the given percentiles (0 - 100) with GainModes::Percentile.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct ColorSettings {
+    pub color_type: ColorTypes,
+    pub range: Option<(f32, f32)>,
+    pub saturation: SaturationColors,
+    pub gain: GainModes,
+    pub percentile: (f32, f32),
+    pub isotherm: Isotherm,
+    pub interpolation: Interpolations,
+    pub scale_factor: usize,
+}
+
+/*
+ * Calibration values from the EEPROM, they differ from sensor to sensor
+ */
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub resolution: Resolutions,
+    pub ct3: i32,
+    pub ct4: i32,
+    pub gain: i32,
+    pub tgc: f32,
+    // (x, y) in the mirrored image as displayed
+    pub bad_pixels: Vec<(usize, usize)>,
+}
+
+/*
+ * Identifies the physical sensor. The device options word at 0x240A
+ * holds the FOV variant, see field_of_view().
+ */
+#[derive(Debug, Clone)]
+pub struct SensorInfo {
+    pub serial: String,
+    pub device_options: u16,
+    pub calibration: Calibration,
+}
+
+lazy_static! {
+    static ref CUSTOM_PALETTES: RwLock<Vec<mlx_palette::Palette>> = RwLock::new(Vec::new());
+    // Only filled once read successfully, a failed read is tried again next time
+    static ref SENSOR_SERIAL: Mutex<Option<String>> = Mutex::new(None);
+    static ref SENSOR_INFO: Mutex<Option<SensorInfo>> = Mutex::new(None);
+    // Last failed serial read, every frame asks for the serial
+    static ref SERIAL_FAILURE: Mutex<Option<(Instant, String)>> = Mutex::new(None);
+    static ref EMISSIVITY: RwLock<f32> = RwLock::new(DEFAULT_EMISSIVITY);
 }
 
 #[derive(Debug)]
@@ -39,20 +192,90 @@ pub struct TemperatureRead {
     pub max_temp: f32,
 }
 
+/*
+ * Interpolated temperatures and their colors,
+ * this is what gets displayed and exported
+ */
+#[derive(Debug, Clone)]
+pub struct ScaledImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub temperatures: Vec<f32>,
+}
+
 #[derive(Debug)]
 pub struct ImageRead {
-    pub pixels: [u8; PIXEL_COUNT * 3],
     pub temperature_read: TemperatureRead,
+    pub color_range: (f32, f32),
+    // Temperatures at evenly spaced positions of the scale, from bottom to top
+    pub scale_ticks: [f32; SCALE_TICK_COUNT],
+    pub scaled: ScaledImage,
+    // Settings the image was colored with, needed to redraw the scale
+    pub settings: ColorSettings,
 }
 
-pub fn take_image(color_type: &ColorTypes) -> Result<ImageRead, String> {
-    let temperature_grid = read_temperatures()?;
+pub fn take_image(settings: &ColorSettings, unit: TemperatureUnits) -> Result<ImageRead, String> {
+    let mut temperature_grid = read_temperatures()?;
+    temperature_grid.convert_unit(TemperatureUnits::Celsius, unit);
 
-    return Ok(mlx_image::color_image(&color_type, &temperature_grid));
+    return Ok(mlx_image::color_image(settings, &temperature_grid));
 }
 
-pub fn get_scale(color_type: ColorTypes) -> [u8; GRADIENT_COUNT * 3] {
-    return mlx_image::color_gradient(color_type);
+pub fn get_scale(settings: &ColorSettings, scale_ticks: &[f32; SCALE_TICK_COUNT]) -> [u8; GRADIENT_COUNT * 3] {
+    return mlx_image::color_gradient(settings, scale_ticks);
+}
+
+/*
+ * Parses the given (file name, content) pairs into custom palettes,
+ * which become available as ColorTypes::Custom afterwards.
+ * Names are matched case insensitive, so a palette named like a built-in
+ * one or an already loaded one is skipped.
+ */
+pub fn load_palettes(files: Vec<(String, String)>) {
+    let mut palettes: Vec<mlx_palette::Palette> = Vec::new();
+
+    for (file_name, content) in files {
+        let palette = match mlx_palette::parse(&file_name, &content) {
+            Ok(palette) => palette,
+            Err(err) => {
+                println!("Failed to load palette {}: {}", file_name, err);
+                continue;
+            },
+        };
+
+        let name = palette.name.to_lowercase();
+
+        if BUILTIN_COLOR_TYPES.iter().any(|c| c.to_string().to_lowercase() == name) {
+            println!("Failed to load palette {}: {} is a built-in color type", file_name, palette.name);
+            continue;
+        }
+
+        if palettes.iter().any(|p| p.name.to_lowercase() == name) {
+            println!("Failed to load palette {}: {} is loaded already", file_name, palette.name);
+            continue;
+        }
+
+        palettes.push(palette);
+    }
+
+    palettes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    *CUSTOM_PALETTES.write().unwrap() = palettes;
+}
+
+pub fn get_custom_palettes() -> Vec<ColorTypes> {
+    let count = CUSTOM_PALETTES.read().unwrap().len();
+    return (0..count).map(ColorTypes::Custom).collect();
+}
+
+pub fn sample_custom_palette(index: usize, temp: f32, min_temp: f32, max_temp: f32) -> [u8; 3] {
+    let palettes = CUSTOM_PALETTES.read().unwrap();
+
+    return match palettes.get(index) {
+        Some(palette) => palette.sample(temp, min_temp, max_temp),
+        None => [0x00; 3],
+    };
 }
 
 pub fn set_framerate(val: Framerates) {
@@ -77,6 +300,115 @@ pub fn read_framerate() -> Result<Framerates, String> {
     return Ok(refresh_rate_raw.try_into().unwrap());
 }
 
+pub fn set_resolution(val: Resolutions) {
+    let resolution_val: u16 = val as u16;
+
+    let mlx_response = bsp_mlx::read_value(REGISTER_CTRL);
+
+    if mlx_response.is_err() { return; }
+    let mut ctrl_register_1 = mlx_response.unwrap();
+
+    ctrl_register_1 &= 0b111_1_00_111_111_1111;
+    ctrl_register_1 |= resolution_val << 10;
+
+    bsp_mlx::write(REGISTER_CTRL, ctrl_register_1).unwrap_or_else(|err| {
+        println!("Resolution update failed: {}", err);
+    });
+}
+
+pub fn read_resolution() -> Result<Resolutions, String> {
+    let ctrl_register = bsp_mlx::read_value(REGISTER_CTRL)?;
+    let resolution_raw = ((ctrl_register >> 10) & 0x3) as u8;
+    return Ok(resolution_raw.try_into().unwrap());
+}
+
+/*
+ * The raw calibration data, one word per address starting at 0x2400
+ */
+pub fn read_eeprom() -> Result<Vec<u16>, String> {
+    return bsp_mlx::read_eeprom();
+}
+
+/*
+ * The three device ID words uniquely identify the sensor
+ */
+fn read_serial() -> Result<String, String> {
+    let mut buf: [u8; 6] = [0x00; 6];
+    bsp_mlx::read(ADDRESS_DEVICE_ID, &mut buf)?;
+
+    let id: Vec<String> = buf
+        .chunks(2)
+        .map(|w| format!("{:04X}", u16::from_be_bytes([w[0], w[1]])))
+        .collect();
+
+    return Ok(id.join(""));
+}
+
+/*
+ * Used for all following temperature calculations
+ */
+pub fn set_emissivity(emissivity: f32) {
+    *EMISSIVITY.write().unwrap() = emissivity.max(MIN_EMISSIVITY).min(1.0);
+}
+
+pub fn get_emissivity() -> f32 {
+    return *EMISSIVITY.read().unwrap();
+}
+
+/*
+ * After a failed read the error is returned for SERIAL_RETRY_INTERVAL
+ * before trying again, so the I2C bus isn't asked on every frame
+ */
+pub fn get_serial() -> Result<String, String> {
+    let mut serial = SENSOR_SERIAL.lock().unwrap();
+
+    if let Some(serial) = serial.as_ref() {
+        return Ok(serial.clone());
+    }
+
+    let mut failure = SERIAL_FAILURE.lock().unwrap();
+
+    if let Some((failed_at, err)) = failure.as_ref() {
+        if failed_at.elapsed() < SERIAL_RETRY_INTERVAL {
+            return Err(err.clone());
+        }
+    }
+
+    return match read_serial() {
+        Ok(read) => {
+            failure.take();
+            serial.replace(read.clone());
+            Ok(read)
+        },
+
+        Err(err) => {
+            failure.replace((Instant::now(), err.clone()));
+            Err(err)
+        },
+    };
+}
+
+fn read_sensor_info() -> Result<SensorInfo, String> {
+    return Ok(SensorInfo {
+        serial: get_serial()?,
+        device_options: bsp_mlx::read_value(ADDRESS_DEVICE_OPTIONS)?,
+        calibration: bsp_mlx::get_calibration()?,
+    });
+}
+
+/*
+ * Read once, none of it changes while running
+ */
+pub fn get_sensor_info() -> Result<SensorInfo, String> {
+    let mut info = SENSOR_INFO.lock().unwrap();
+
+    if info.is_none() {
+        info.replace(read_sensor_info()?);
+    }
+
+    return Ok(info.clone().unwrap());
+}
+
 pub fn read_temperatures() -> Result<TemperatureRead, String> {
     let image_raw = read_raw_image()?;
     let image_eval = bsp_mlx::evaluate_image(image_raw)?;
@@ -112,6 +444,13 @@ pub fn read_temperatures() -> Result<TemperatureRead, String> {
     })
 }
 
+/*
+ * Ambient temperature in Celsius, measured by the sensor together with the last frame
+ */
+pub fn read_ambient_temperature() -> Result<f32, String> {
+    return bsp_mlx::read_ambient_temperature();
+}
+
 fn read_raw_image() -> Result<[u16; PIXEL_COUNT], String> {
     let mut img: [u16; PIXEL_COUNT] = [0x00; PIXEL_COUNT];
 
@@ -167,11 +506,111 @@ impl Default for TemperatureRead {
     }
 }
 
+impl SensorInfo {
+    /*
+     * None for option bits that match no known variant
+     */
+    pub fn field_of_view(&self) -> Option<&'static str> {
+        let bits = self.device_options & FOV_MASK;
+
+        return FOV_VARIANTS
+            .iter()
+            .find(|(value, _)| *value == bits)
+            .map(|(_, name)| *name);
+    }
+
+    /*
+     * Name and value pairs for display
+     */
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let c = &self.calibration;
+
+        let bad_pixels = match c.bad_pixels.is_empty() {
+            true => "None".to_string(),
+            false => c.bad_pixels
+                .iter()
+                .map(|(x, y)| format!("({}, {})", x, y))
+                .collect::<Vec<String>>()
+                .join(", "),
+        };
+
+        return vec![
+            ("Serial", self.serial.clone()),
+            ("Device options", format!("0x{:04X}", self.device_options)),
+            ("Field of view", self.field_of_view().unwrap_or("Unknown").to_string()),
+            ("Calibrated at", format!("{} bit", c.resolution.to_string())),
+            ("CT3 / CT4", format!("{} / {} °C", c.ct3, c.ct4)),
+            ("GAIN", c.gain.to_string()),
+            ("TGC", format!("{:.3}", c.tgc)),
+            ("Bad pixels", bad_pixels),
+        ];
+    }
+}
+
+impl TemperatureRead {
+    pub fn convert_unit(&mut self, from: TemperatureUnits, to: TemperatureUnits) {
+        if from == to { return; }
+
+        for t in self.temperature_grid.iter_mut() {
+            *t = to.from_celsius(from.to_celsius(*t));
+        }
+
+        self.min_temp = to.from_celsius(from.to_celsius(self.min_temp));
+        self.max_temp = to.from_celsius(from.to_celsius(self.max_temp));
+    }
+}
+
+impl Default for ScaledImage {
+    fn default() -> Self {
+        ScaledImage {
+            width: PIXELS_WIDTH,
+            height: PIXELS_HEIGHT,
+            pixels: vec![0x00; PIXEL_COUNT * 3],
+            temperatures: vec![0.0; PIXEL_COUNT],
+        }
+    }
+}
+
+impl ScaledImage {
+    /*
+     * Temperature at the relative position (0 - 1) inside the image
+     */
+    pub fn get_temperature(&self, u: f32, v: f32) -> f32 {
+        let x = (u * self.width as f32).floor().max(0.0).min(self.width as f32 - 1.0) as usize;
+        let y = (v * self.height as f32).floor().max(0.0).min(self.height as f32 - 1.0) as usize;
+
+        return self.temperatures[y * self.width + x];
+    }
+}
+
 impl Default for ImageRead {
     fn default() -> Self {
         ImageRead {
-            pixels: [0x00; PIXEL_COUNT * 3],
-            temperature_read: TemperatureRead::default()
+            temperature_read: TemperatureRead::default(),
+            color_range: (0.0, 0.0),
+            scale_ticks: [0.0; SCALE_TICK_COUNT],
+            scaled: ScaledImage::default(),
+            settings: ColorSettings::default(),
+        }
+    }
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        ColorSettings {
+            color_type: ColorTypes::Hue,
+            range: None,
+            saturation: SaturationColors::Off,
+            gain: GainModes::Linear,
+            percentile: (1.0, 99.0),
+            isotherm: Isotherm {
+                mode: IsothermModes::Off,
+                range: (30.0, 40.0),
+                color: IsothermColors::Green,
+                gray_background: false,
+            },
+            interpolation: Interpolations::Nearest,
+            scale_factor: 1,
         }
     }
 }
@@ -181,7 +620,11 @@ impl ToString for ColorTypes {
         match self {
             ColorTypes::Gray => "Gray".into(),
             ColorTypes::Cheap => "Cheap".into(),
-            ColorTypes::Hue => "Hue".into()
+            ColorTypes::Hue => "Hue".into(),
+            ColorTypes::Custom(index) => CUSTOM_PALETTES.read().unwrap()
+                .get(*index)
+                .map(|p| p.name.clone())
+                .unwrap_or("Unknown".into()),
         }
     }
 }
@@ -195,7 +638,258 @@ impl FromStr for ColorTypes {
             "cheap" => Ok(ColorTypes::Cheap),
             "hue" => Ok(ColorTypes::Hue),
 
-            _ => Err("Unrecognised color type")
+            name => CUSTOM_PALETTES.read().unwrap()
+                .iter()
+                .position(|p| p.name.to_lowercase() == name)
+                .map(ColorTypes::Custom)
+                .ok_or("Unrecognised color type")
+        }
+    }
+}
+
+impl ToString for RangeModes {
+    fn to_string(&self) -> String {
+        match self {
+            RangeModes::Auto => "Auto".into(),
+            RangeModes::Locked => "Locked".into(),
+            RangeModes::Manual => "Manual".into(),
+        }
+    }
+}
+
+impl FromStr for RangeModes {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(RangeModes::Auto),
+            "locked" | "lock" => Ok(RangeModes::Locked),
+            "manual" => Ok(RangeModes::Manual),
+
+            _ => Err("Unrecognised range mode")
+        }
+    }
+}
+
+impl ToString for GainModes {
+    fn to_string(&self) -> String {
+        match self {
+            GainModes::Linear => "Linear".into(),
+            GainModes::Percentile => "Percentile".into(),
+            GainModes::Equalized => "Equalized".into(),
+        }
+    }
+}
+
+impl FromStr for GainModes {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(GainModes::Linear),
+            "percentile" => Ok(GainModes::Percentile),
+            "equalized" | "equalize" | "histogram" => Ok(GainModes::Equalized),
+
+            _ => Err("Unrecognised gain mode")
+        }
+    }
+}
+
+impl Isotherm {
+    pub fn contains(&self, temp: f32) -> bool {
+        return match self.mode {
+            IsothermModes::Off => false,
+            IsothermModes::Band => temp >= self.range.0 && temp <= self.range.1,
+            IsothermModes::Above => temp >= self.range.0,
+            IsothermModes::Below => temp <= self.range.1,
+        };
+    }
+}
+
+impl ToString for IsothermModes {
+    fn to_string(&self) -> String {
+        match self {
+            IsothermModes::Off => "Off".into(),
+            IsothermModes::Band => "Band".into(),
+            IsothermModes::Above => "Above".into(),
+            IsothermModes::Below => "Below".into(),
+        }
+    }
+}
+
+impl FromStr for IsothermModes {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(IsothermModes::Off),
+            "band" => Ok(IsothermModes::Band),
+            "above" => Ok(IsothermModes::Above),
+            "below" => Ok(IsothermModes::Below),
+
+            _ => Err("Unrecognised isotherm mode")
+        }
+    }
+}
+
+impl IsothermColors {
+    pub fn rgb(&self) -> [u8; 3] {
+        return match self {
+            IsothermColors::Green => [0x00, 0xFF, 0x00],
+            IsothermColors::Magenta => [0xFF, 0x00, 0xFF],
+            IsothermColors::Cyan => [0x00, 0xFF, 0xFF],
+        };
+    }
+}
+
+impl ToString for IsothermColors {
+    fn to_string(&self) -> String {
+        match self {
+            IsothermColors::Green => "Green".into(),
+            IsothermColors::Magenta => "Magenta".into(),
+            IsothermColors::Cyan => "Cyan".into(),
+        }
+    }
+}
+
+impl FromStr for IsothermColors {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "green" => Ok(IsothermColors::Green),
+            "magenta" => Ok(IsothermColors::Magenta),
+            "cyan" => Ok(IsothermColors::Cyan),
+
+            _ => Err("Unrecognised isotherm color")
+        }
+    }
+}
+
+impl ToString for Interpolations {
+    fn to_string(&self) -> String {
+        match self {
+            Interpolations::Nearest => "Nearest".into(),
+            Interpolations::Bilinear => "Bilinear".into(),
+            Interpolations::Bicubic => "Bicubic".into(),
+            Interpolations::Lanczos => "Lanczos".into(),
+        }
+    }
+}
+
+impl FromStr for Interpolations {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nearest" | "none" => Ok(Interpolations::Nearest),
+            "bilinear" => Ok(Interpolations::Bilinear),
+            "bicubic" => Ok(Interpolations::Bicubic),
+            "lanczos" => Ok(Interpolations::Lanczos),
+
+            _ => Err("Unrecognised interpolation")
+        }
+    }
+}
+
+impl SaturationColors {
+    /*
+     * Color for temperatures outside of the range,
+     * None if the temperature is inside or saturation colors are off
+     */
+    pub fn get_color(&self, temp: f32, min_temp: f32, max_temp: f32) -> Option<[u8; 3]> {
+        let (below, above) = match self {
+            SaturationColors::Off => return None,
+            SaturationColors::BlackWhite => ([0x00; 3], [0xFF; 3]),
+            SaturationColors::WhiteBlack => ([0xFF; 3], [0x00; 3]),
+        };
+
+        if temp < min_temp { return Some(below); }
+        if temp > max_temp { return Some(above); }
+
+        return None;
+    }
+}
+
+impl ToString for SaturationColors {
+    fn to_string(&self) -> String {
+        match self {
+            SaturationColors::Off => "Off".into(),
+            SaturationColors::BlackWhite => "Black/White".into(),
+            SaturationColors::WhiteBlack => "White/Black".into(),
+        }
+    }
+}
+
+impl FromStr for SaturationColors {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(SaturationColors::Off),
+            "black/white" | "blackwhite" => Ok(SaturationColors::BlackWhite),
+            "white/black" | "whiteblack" => Ok(SaturationColors::WhiteBlack),
+
+            _ => Err("Unrecognised saturation colors")
+        }
+    }
+}
+
+impl TemperatureUnits {
+    pub fn from_celsius(&self, temp: f32) -> f32 {
+        match self {
+            TemperatureUnits::Celsius => temp,
+            TemperatureUnits::Fahrenheit => temp * 1.8 + 32.0,
+            TemperatureUnits::Kelvin => temp + 273.15,
+        }
+    }
+
+    pub fn to_celsius(&self, temp: f32) -> f32 {
+        match self {
+            TemperatureUnits::Celsius => temp,
+            TemperatureUnits::Fahrenheit => (temp - 32.0) / 1.8,
+            TemperatureUnits::Kelvin => temp - 273.15,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnits::Celsius => "°C",
+            TemperatureUnits::Fahrenheit => "°F",
+            TemperatureUnits::Kelvin => "K",
+        }
+    }
+
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "°C" => Some(TemperatureUnits::Celsius),
+            "°F" => Some(TemperatureUnits::Fahrenheit),
+            "K" => Some(TemperatureUnits::Kelvin),
+            _ => None,
+        }
+    }
+}
+
+impl ToString for TemperatureUnits {
+    fn to_string(&self) -> String {
+        match self {
+            TemperatureUnits::Celsius => "Celsius".into(),
+            TemperatureUnits::Fahrenheit => "Fahrenheit".into(),
+            TemperatureUnits::Kelvin => "Kelvin".into(),
+        }
+    }
+}
+
+impl FromStr for TemperatureUnits {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "c" | "celsius" => Ok(TemperatureUnits::Celsius),
+            "f" | "fahrenheit" => Ok(TemperatureUnits::Fahrenheit),
+            "k" | "kelvin" => Ok(TemperatureUnits::Kelvin),
+
+            _ => Err("Unrecognised temperature unit")
         }
     }
 }
@@ -212,6 +906,53 @@ impl Framerates {
         let new_val = (current_val - 1).max(0) as u8;
         return new_val.try_into().unwrap();
     }
+
+    /*
+     * The framerate is given per subpage, a full frame consists of two subpages
+     */
+    pub fn frame_period(&self) -> Duration {
+        let subpages_per_second = 2.0_f32.powi(*self as i32 - 1);
+        return Duration::from_secs_f32(2.0 / subpages_per_second);
+    }
+}
+
+impl TryFrom<u8> for Resolutions {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0b00 => Ok(Resolutions::Sixteen),
+            0b01 => Ok(Resolutions::Seventeen),
+            0b10 => Ok(Resolutions::Eighteen),
+            0b11 => Ok(Resolutions::Nineteen),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Resolutions {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "16" => Ok(Resolutions::Sixteen),
+            "17" => Ok(Resolutions::Seventeen),
+            "18" => Ok(Resolutions::Eighteen),
+            "19" => Ok(Resolutions::Nineteen),
+            _ => Err("Unrecognised resolution. Only 16 to 19 bits are allowed")
+        }
+    }
+}
+
+impl ToString for Resolutions {
+    fn to_string(&self) -> String {
+        match self {
+            Resolutions::Sixteen => "16".into(),
+            Resolutions::Seventeen => "17".into(),
+            Resolutions::Eighteen => "18".into(),
+            Resolutions::Nineteen => "19".into(),
+        }
+    }
 }
 
 impl TryFrom<u8> for Framerates {
@@ -264,3 +1005,76 @@ impl ToString for Framerates {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_palettes_named_like_others() {
+        let ramp = "0,0,0\n255,255,255\n".to_string();
+
+        load_palettes(vec![
+            ("Hue.csv".to_string(), ramp.clone()),
+            ("warm.csv".to_string(), ramp.clone()),
+            ("Warm.json".to_string(), "[[0, 0, 0], [255, 0, 0]]".to_string()),
+        ]);
+
+        assert_eq!(get_custom_palettes().len(), 1);
+        assert!(matches!("hue".parse::<ColorTypes>(), Ok(ColorTypes::Hue)));
+        assert!(matches!("WARM".parse::<ColorTypes>(), Ok(ColorTypes::Custom(0))));
+
+        load_palettes(Vec::new());
+    }
+
+    fn sensor_info(device_options: u16) -> SensorInfo {
+        return SensorInfo {
+            serial: "0123-4567-89AB".to_string(),
+            device_options,
+            calibration: Calibration {
+                resolution: Resolutions::Eighteen,
+                ct3: 40,
+                ct4: 160,
+                gain: 6028,
+                tgc: -0.125,
+                bad_pixels: vec![(31, 0), (4, 12)],
+            },
+        };
+    }
+
+    #[test]
+    fn decodes_field_of_view() {
+        let cases = [
+            (0x0001, Some("BAA 110 x 75°")),
+            (0x0002, Some("BAB 55 x 35°")),
+            (0x1201, Some("BAA 110 x 75°")),
+            (0x1202, Some("BAB 55 x 35°")),
+            (0x0000, None),
+            (0x0003, None),
+        ];
+
+        for (device_options, expected) in cases {
+            assert_eq!(sensor_info(device_options).field_of_view(), expected, "0x{:04X}", device_options);
+        }
+    }
+
+    #[test]
+    fn summarizes_calibration() {
+        let summary = sensor_info(0x0003).summary();
+        let value = |name: &str| summary.iter().find(|(n, _)| *n == name).unwrap().1.clone();
+
+        assert_eq!(value("Device options"), "0x0003");
+        assert_eq!(value("Field of view"), "Unknown");
+        assert_eq!(value("Calibrated at"), "18 bit");
+        assert_eq!(value("CT3 / CT4"), "40 / 160 °C");
+        assert_eq!(value("GAIN"), "6028");
+        assert_eq!(value("TGC"), "-0.125");
+        assert_eq!(value("Bad pixels"), "(31, 0), (4, 12)");
+
+        let mut info = sensor_info(0x0002);
+        info.calibration.bad_pixels.clear();
+        let summary = info.summary();
+        assert!(summary.contains(&("Field of view", "BAB 55 x 35°".to_string())));
+        assert!(summary.contains(&("Bad pixels", "None".to_string())));
+    }
+}
@@ -11,6 +11,10 @@ const CAM_ADDR: u8 = 0x33;
 pub const REGISTER_STATUS: u16 = 0x8000;
 pub const REGISTER_CTRL: u16 = 0x800D;
 pub const ADDRESS_RAM: u16 = 0x0400;
+pub const ADDRESS_DEVICE_ID: u16 = 0x2407;
+pub const ADDRESS_DEVICE_OPTIONS: u16 = 0x240A;
+pub const ADDRESS_EEPROM: u16 = 0x2400;
+pub const EEPROM_WORDS: usize = 832;
 
 lazy_static! {
     static ref I2C_MUTEX: Mutex<u32> = Mutex::new(0);
@@ -77,3 +81,23 @@ pub fn read_value(address: u16) -> Result<u16, String> {
 pub fn evaluate_image(pix_data: [u16; PIXEL_COUNT]) -> Result<[f32; PIXEL_COUNT], String> {
     return mlx_eeprom::evaluate(pix_data);
 }
+
+pub fn get_calibration() -> Result<super::Calibration, String> {
+    return mlx_eeprom::calibration();
+}
+
+pub fn read_ambient_temperature() -> Result<f32, String> {
+    return mlx_eeprom::ambient_temperature();
+}
+
+pub fn read_eeprom() -> Result<Vec<u16>, String> {
+    let mut buf: [u8; EEPROM_WORDS * 2] = [0x00; EEPROM_WORDS * 2];
+    read(ADDRESS_EEPROM, &mut buf)?;
+
+    let res = buf
+        .chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]))
+        .collect();
+
+    return Ok(res);
+}
@@ -2,6 +2,8 @@
 
 use lazy_static::lazy_static;
 
+use super::super::{Calibration, get_emissivity};
+
 const PIXELS_WIDTH: usize = 32;
 const PIXELS_HEIGHT: usize = 24;
 const PIXEL_COUNT: usize = PIXELS_WIDTH * PIXELS_HEIGHT;
@@ -85,10 +87,10 @@ pub fn evaluate(pix_data: [u16; PIXEL_COUNT]) -> Result<[f32; PIXEL_COUNT], Stri
     if EEPROM_VARS.is_err() { return Err("Eeprom Variables not restored.".to_string()); }
     let eeprom_vars = EEPROM_VARS.as_ref().unwrap();
 
-    const EMISSIVITY: f32 = 1.0;
-
     let Resolution_corr: f32 = 2_f32.powi(eeprom_vars.Resolution as i32) / 2_f32.powi((super::read_value(0x800D)? as i32 & 0x0C00) >> 10);
 
+    let EMISSIVITY = get_emissivity();
+
     // Calculate Voltage
     let V_dd = calc_V_dd(Resolution_corr)?;
 
@@ -241,6 +243,34 @@ pub fn restore() -> Result<EepromVars, String> {
     });
 }
 
+pub fn calibration() -> Result<Calibration, String> {
+    let eeprom_vars = EEPROM_VARS.as_ref()?;
+
+    let bad_pixels = eeprom_vars.bad_pixels
+        .iter()
+        .filter(|&&i| i < PIXEL_COUNT)
+        .map(|&i| (PIXELS_WIDTH - i % PIXELS_WIDTH - 1, i / PIXELS_WIDTH))
+        .collect();
+
+    return Ok(Calibration {
+        resolution: (eeprom_vars.Resolution as u8).try_into().unwrap(),
+        ct3: eeprom_vars.CT3,
+        ct4: eeprom_vars.CT4,
+        gain: eeprom_vars.GAIN,
+        tgc: eeprom_vars.TGC,
+        bad_pixels,
+    });
+}
+
+/*
+ * Sensor die temperature of the last measurement
+ */
+pub fn ambient_temperature() -> Result<f32, String> {
+    if EEPROM_VARS.is_err() { return Err("Eeprom Variables not restored.".to_string()); }
+
+    return calc_T_a();
+}
+
 // -------------------------------------
 // | Temperature calculation functions |
 // -------------------------------------
@@ -977,3 +1007,31 @@ fn restore_bad_pixels(eeprom_raw: [u16; EEPROM_SIZE]) -> [usize; 4] {
 
     return bad_pixels;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_eeprom_val(address: u16, value: u16, eeprom_raw: &mut [u16; EEPROM_SIZE]) {
+        eeprom_raw[(address - 0x2410) as usize] = value;
+    }
+
+    #[test]
+    fn restores_calibration_fields() {
+        let mut eeprom_raw: [u16; EEPROM_SIZE] = [0x00; EEPROM_SIZE];
+        set_eeprom_val(0x2430, 0xE890, &mut eeprom_raw);
+        set_eeprom_val(0x243C, 0x00FC, &mut eeprom_raw);
+        set_eeprom_val(0x243F, 0x2840, &mut eeprom_raw);
+        set_eeprom_val(0x2440 + 0, 0x0001, &mut eeprom_raw);
+        set_eeprom_val(0x2440 + 388, 0xFF41, &mut eeprom_raw);
+
+        assert_eq!(restore_gain(eeprom_raw), -6000);
+        assert_eq!(restore_TGC(eeprom_raw), -0.125);
+
+        let step = restore_Step(eeprom_raw);
+        let ct3 = restore_CT3(step, eeprom_raw);
+        assert_eq!((ct3, restore_CT4(step, ct3, eeprom_raw)), (80, 240));
+
+        assert_eq!(restore_bad_pixels(eeprom_raw), [0, 388, usize::MAX, usize::MAX]);
+    }
+}
@@ -1,6 +1,7 @@
 use super::{
-    PIXEL_COUNT, GRADIENT_WIDTH, GRADIENT_HEIGHT, GRADIENT_COUNT,
-    TemperatureRead, ImageRead, ColorTypes
+    GRADIENT_WIDTH, GRADIENT_HEIGHT, GRADIENT_COUNT, SCALE_TICK_COUNT, MAX_SCALE_FACTOR,
+    PIXELS_WIDTH, PIXELS_HEIGHT, TemperatureRead, ImageRead, ScaledImage,
+    ColorTypes, ColorSettings, GainModes, IsothermModes, sample_custom_palette, mlx_interpolate
 };
 
 /*
@@ -88,42 +89,147 @@ fn rgb_hue_function(temp: f32, min_temp: f32, max_temp :f32) -> [u8; 3] {
     return [r_byte, g_byte, b_byte];
 }
 
-pub fn color_image(color_type: &ColorTypes, temperatures: &TemperatureRead) -> ImageRead {
-    let mut res_pixels: [u8; PIXEL_COUNT * 3] = [0x00; PIXEL_COUNT * 3];
-    let min_temp = temperatures.min_temp;
-    let max_temp = temperatures.max_temp;
+fn color_function(color_type: ColorTypes, temp: f32, min_temp: f32, max_temp: f32) -> [u8; 3] {
+    return match color_type {
+        ColorTypes::Gray => grayscale_function(temp, min_temp, max_temp),
+        ColorTypes::Cheap => rgb_cheap_function(temp, min_temp, max_temp),
+        ColorTypes::Hue => rgb_hue_function(temp, min_temp, max_temp),
+        ColorTypes::Custom(index) => sample_custom_palette(index, temp, min_temp, max_temp),
+    };
+}
 
-    for i in 0..PIXEL_COUNT {
-        let index = i * 3;
-        let temp = temperatures.temperature_grid[i];
+/*
+ * Returns the temperature at the given percentile (0 - 100)
+ */
+fn percentile(sorted_temps: &[f32], p: f32) -> f32 {
+    let index = (p / 100.0 * (sorted_temps.len() - 1) as f32).round() as usize;
+    return sorted_temps[index.min(sorted_temps.len() - 1)];
+}
 
-        let color = match color_type {
-            ColorTypes::Gray => grayscale_function(temp, min_temp, max_temp),
-            ColorTypes::Cheap => rgb_cheap_function(temp, min_temp, max_temp),
-            ColorTypes::Hue => rgb_hue_function(temp, min_temp, max_temp),
-        };
+/*
+ * Maps temperature to its position in the cumulative histogram,
+ * so every color of the palette covers roughly the same amount of pixels
+ */
+fn equalize(temp: f32, sorted_temps: &[f32]) -> f32 {
+    if sorted_temps.len() < 2 { return 0.5; }
+
+    let count_below = sorted_temps.partition_point(|t| *t <= temp);
+    return count_below.saturating_sub(1) as f32 / (sorted_temps.len() - 1) as f32;
+}
 
-        res_pixels[index..index+3].copy_from_slice(&color);
+fn calc_scale_ticks(gain: GainModes, min_temp: f32, max_temp: f32, sorted_temps: &[f32]) -> [f32; SCALE_TICK_COUNT] {
+    let mut ticks = [0.0; SCALE_TICK_COUNT];
+
+    for (i, tick) in ticks.iter_mut().enumerate() {
+        let t = i as f32 / (SCALE_TICK_COUNT - 1) as f32;
+
+        *tick = match gain {
+            GainModes::Equalized if !sorted_temps.is_empty() => percentile(sorted_temps, t * 100.0),
+            _ => min_temp + t * (max_temp - min_temp),
+        };
     }
 
+    return ticks;
+}
+
+pub fn color_image(settings: &ColorSettings, temperatures: &TemperatureRead) -> ImageRead {
+    let mut sorted_temps = temperatures.temperature_grid;
+    sorted_temps.sort_by(|a, b| a.total_cmp(b));
+
+    let (min_temp, max_temp) = match (settings.range, settings.gain) {
+        (Some(range), _) => range,
+        (None, GainModes::Percentile) => (
+            percentile(&sorted_temps, settings.percentile.0),
+            percentile(&sorted_temps, settings.percentile.1),
+        ),
+        (None, _) => (temperatures.min_temp, temperatures.max_temp),
+    };
+
+    // Only temperatures inside of the range take part in equalization
+    let lower = sorted_temps.partition_point(|t| *t < min_temp);
+    let upper = sorted_temps.partition_point(|t| *t <= max_temp);
+    let temps_in_range = &sorted_temps[lower..upper.max(lower)];
+
+    let color_type = background_color_type(settings);
+
+    let color_pixel = |temp: f32| -> [u8; 3] {
+        if settings.isotherm.contains(temp) {
+            return settings.isotherm.color.rgb();
+        }
+
+        return settings.saturation
+            .get_color(temp, min_temp, max_temp)
+            .unwrap_or_else(|| match settings.gain {
+                GainModes::Equalized => color_function(color_type, equalize(temp, temps_in_range), 0.0, 1.0),
+                _ => color_function(color_type, temp, min_temp, max_temp),
+            });
+    };
+
+    // Interpolate temperatures first, then color them like the original pixels
+    let scale_factor = settings.scale_factor.max(1).min(MAX_SCALE_FACTOR);
+
+    let scaled_temperatures = mlx_interpolate::scale_grid(
+        &temperatures.temperature_grid,
+        settings.interpolation,
+        scale_factor
+    );
+
+    let scaled_pixels: Vec<u8> = scaled_temperatures
+        .iter()
+        .flat_map(|temp| color_pixel(*temp))
+        .collect();
+
     return ImageRead {
-        pixels: res_pixels,
         temperature_read: TemperatureRead {
             temperature_grid: temperatures.temperature_grid,
-            min_temp,
-            max_temp,
+            min_temp: temperatures.min_temp,
+            max_temp: temperatures.max_temp,
         },
+        color_range: (min_temp, max_temp),
+        scale_ticks: calc_scale_ticks(settings.gain, min_temp, max_temp, temps_in_range),
+        scaled: ScaledImage {
+            width: PIXELS_WIDTH * scale_factor,
+            height: PIXELS_HEIGHT * scale_factor,
+            pixels: scaled_pixels,
+            temperatures: scaled_temperatures,
+        },
+        settings: *settings,
     }
 }
 
-pub fn color_gradient(color_type: ColorTypes) -> [u8; GRADIENT_COUNT * 3] {
+/*
+ * The scene outside of an isotherm may be shown in grayscale
+ */
+fn background_color_type(settings: &ColorSettings) -> ColorTypes {
+    if settings.isotherm.mode != IsothermModes::Off && settings.isotherm.gray_background {
+        return ColorTypes::Gray;
+    }
+
+    return settings.color_type;
+}
+
+/*
+ * Linearly interpolates between the scale ticks,
+ * t = 0 is the bottom of the scale
+ */
+fn scale_temperature(t: f32, scale_ticks: &[f32; SCALE_TICK_COUNT]) -> f32 {
+    let pos = t.max(0.0).min(1.0) * (SCALE_TICK_COUNT - 1) as f32;
+    let lower = (pos.floor() as usize).min(SCALE_TICK_COUNT - 2);
+    let f = pos - lower as f32;
+
+    return scale_ticks[lower] + (scale_ticks[lower + 1] - scale_ticks[lower]) * f;
+}
+
+pub fn color_gradient(settings: &ColorSettings, scale_ticks: &[f32; SCALE_TICK_COUNT]) -> [u8; GRADIENT_COUNT * 3] {
     let mut gradient: [u8; GRADIENT_COUNT * 3] = [0x00; GRADIENT_COUNT * 3];
+    let color_type = background_color_type(settings);
+
     for y in 0..GRADIENT_HEIGHT {
         let t = 1.0 - y as f32 / GRADIENT_HEIGHT as f32;
-        let color = match color_type {
-            ColorTypes::Gray => grayscale_function(t, 0.0, 1.0),
-            ColorTypes::Cheap => rgb_cheap_function(t, 0.0, 1.0),
-            ColorTypes::Hue => rgb_hue_function(t, 0.0, 1.0),
+
+        let color = match settings.isotherm.contains(scale_temperature(t, scale_ticks)) {
+            true => settings.isotherm.color.rgb(),
+            false => color_function(color_type, t, 0.0, 1.0),
         };
 
         for x in 0..GRADIENT_WIDTH {
@@ -0,0 +1,121 @@
+use std::f32::consts::PI;
+
+use super::{PIXELS_WIDTH, PIXELS_HEIGHT, PIXEL_COUNT, Interpolations};
+
+/*
+ * Upscales the temperature grid by the given factor.
+ * Each axis is resampled separately, pixels outside of
+ * the grid repeat the nearest edge pixel.
+ */
+pub fn scale_grid(grid: &[f32; PIXEL_COUNT], interpolation: Interpolations, factor: usize) -> Vec<f32> {
+    let factor = factor.max(1);
+
+    let (kernel, radius): (fn(f32) -> f32, i32) = match interpolation {
+        Interpolations::Nearest => (nearest_kernel, 1),
+        Interpolations::Bilinear => (bilinear_kernel, 1),
+        Interpolations::Bicubic => (bicubic_kernel, 2),
+        Interpolations::Lanczos => (lanczos_kernel, 3),
+    };
+
+    let out_width = PIXELS_WIDTH * factor;
+    let out_height = PIXELS_HEIGHT * factor;
+
+    let x_weights = axis_weights(PIXELS_WIDTH, factor, kernel, radius);
+    let y_weights = axis_weights(PIXELS_HEIGHT, factor, kernel, radius);
+
+    // Horizontal pass
+    let mut rows = vec![0.0; PIXELS_HEIGHT * out_width];
+
+    for y in 0..PIXELS_HEIGHT {
+        for x in 0..out_width {
+            rows[y * out_width + x] = x_weights[x]
+                .iter()
+                .map(|(i, w)| grid[y * PIXELS_WIDTH + i] * w)
+                .sum();
+        }
+    }
+
+    // Vertical pass
+    let mut res = vec![0.0; out_width * out_height];
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            res[y * out_width + x] = y_weights[y]
+                .iter()
+                .map(|(i, w)| rows[i * out_width + x] * w)
+                .sum();
+        }
+    }
+
+    return res;
+}
+
+/*
+ * For every output coordinate, lists the source pixels
+ * and their normalized weights
+ */
+fn axis_weights(size: usize, factor: usize, kernel: fn(f32) -> f32, radius: i32) -> Vec<Vec<(usize, f32)>> {
+    let mut res = Vec::with_capacity(size * factor);
+
+    for o in 0..size * factor {
+        // Align pixel centers of source and output
+        let center = (o as f32 + 0.5) / factor as f32 - 0.5;
+        let base = center.floor() as i32;
+
+        let mut weights = Vec::with_capacity(radius as usize * 2);
+        let mut sum = 0.0;
+
+        for i in (base - radius + 1)..=(base + radius) {
+            let w = kernel(center - i as f32);
+            let index = i.max(0).min(size as i32 - 1) as usize;
+
+            weights.push((index, w));
+            sum += w;
+        }
+
+        if sum != 0.0 {
+            for (_, w) in weights.iter_mut() {
+                *w /= sum;
+            }
+        }
+
+        res.push(weights);
+    }
+
+    return res;
+}
+
+fn nearest_kernel(x: f32) -> f32 {
+    if x > -0.5 && x <= 0.5 { 1.0 } else { 0.0 }
+}
+
+fn bilinear_kernel(x: f32) -> f32 {
+    return (1.0 - x.abs()).max(0.0);
+}
+
+/*
+ * Keys' cubic convolution with a = -0.5 (Catmull-Rom)
+ */
+fn bicubic_kernel(x: f32) -> f32 {
+    const A: f32 = -0.5;
+    let x = x.abs();
+
+    if x <= 1.0 {
+        return (A + 2.0) * x.powi(3) - (A + 3.0) * x.powi(2) + 1.0;
+    }
+    if x < 2.0 {
+        return A * x.powi(3) - 5.0 * A * x.powi(2) + 8.0 * A * x - 4.0 * A;
+    }
+
+    return 0.0;
+}
+
+fn lanczos_kernel(x: f32) -> f32 {
+    const A: f32 = 3.0;
+
+    if x == 0.0 { return 1.0; }
+    if x.abs() >= A { return 0.0; }
+
+    let px = PI * x;
+    return A * px.sin() * (px / A).sin() / (px * px);
+}
@@ -0,0 +1,390 @@
+use serde::Deserialize;
+
+pub const LUT_SIZE: usize = 256;
+
+// GIMP writes positions with 6 decimals, neighbouring segments may overlap by that much
+const GGR_TOLERANCE: f32 = 0.000001;
+
+/*
+ * A user supplied color palette, sampled into a lookup table on load.
+ * The name is the file name without extension, so it can be used
+ * as color type in the options file and on the command line.
+ */
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub name: String,
+    lut: [[u8; 3]; LUT_SIZE],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ColorStop {
+    position: f32,
+    color: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GimpSegment {
+    left: f32,
+    middle: f32,
+    right: f32,
+    color_left: [f32; 3],
+    color_right: [f32; 3],
+    blending: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonPalette {
+    Stops { stops: Vec<JsonStop> },
+    Colors(Vec<[f32; 3]>),
+}
+
+#[derive(Deserialize)]
+struct JsonStop {
+    position: f32,
+    color: [f32; 3],
+}
+
+impl Palette {
+    pub fn sample(&self, temp: f32, min_temp: f32, max_temp: f32) -> [u8; 3] {
+        let mut t = (temp - min_temp) / (max_temp - min_temp);
+        t = t.max(0.0).min(1.0);
+
+        // NaN (min_temp == max_temp) lands on the first entry
+        let index = (t * (LUT_SIZE - 1) as f32).round() as usize;
+        return self.lut[index.min(LUT_SIZE - 1)];
+    }
+}
+
+/*
+ * Parses a palette file, the format is chosen by file extension:
+ * .csv  - one stop per line, "position,r,g,b" or "r,g,b" (evenly spaced)
+ * .json - {"stops": [{"position": 0.0, "color": [r, g, b]}, ...]} or [[r, g, b], ...]
+ * .ggr  - GIMP gradient
+ * Colors are given in 0-255, positions in 0-1.
+ */
+pub fn parse(file_name: &str, content: &str) -> Result<Palette, String> {
+    let (name, extension) = match file_name.rsplit_once('.') {
+        Some(parts) => parts,
+        None => return Err(format!("{}: Missing file extension", file_name)),
+    };
+
+    let lut = match extension.to_lowercase().as_str() {
+        "csv" => sample_stops(check_stops(parse_csv(content)?)?),
+        "json" => sample_stops(check_stops(parse_json(content)?)?),
+        "ggr" => sample_gimp(parse_ggr(content)?),
+
+        _ => return Err(format!("{}: Unsupported palette format", file_name)),
+    };
+
+    return Ok(Palette {
+        name: name.to_string(),
+        lut,
+    });
+}
+
+fn parse_csv(content: &str) -> Result<Vec<ColorStop>, String> {
+    let mut rows: Vec<Vec<f32>> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let values: Result<Vec<f32>, _> = line
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect();
+
+        // Allow a header line like "position,r,g,b"
+        if values.is_err() && rows.is_empty() { continue; }
+
+        let values = values.map_err(|_| format!("Invalid CSV line: {}", line))?;
+        rows.push(values);
+    }
+
+    let count = rows.len();
+    let mut stops = Vec::with_capacity(count);
+
+    for (i, row) in rows.iter().enumerate() {
+        let stop = match row.len() {
+            3 => ColorStop {
+                position: i as f32 / (count - 1).max(1) as f32,
+                color: [row[0], row[1], row[2]],
+            },
+            4 => ColorStop {
+                position: row[0],
+                color: [row[1], row[2], row[3]],
+            },
+            _ => return Err(format!("Expected 3 or 4 values per CSV line, got {}", row.len())),
+        };
+
+        stops.push(stop);
+    }
+
+    return Ok(stops);
+}
+
+fn parse_json(content: &str) -> Result<Vec<ColorStop>, String> {
+    let palette: JsonPalette = serde_json::from_str(content)
+        .map_err(|err| format!("Invalid JSON palette: {}", err))?;
+
+    let stops = match palette {
+        JsonPalette::Stops { stops } => stops
+            .iter()
+            .map(|s| ColorStop { position: s.position, color: s.color })
+            .collect(),
+
+        JsonPalette::Colors(colors) => {
+            let count = colors.len();
+            colors
+                .iter()
+                .enumerate()
+                .map(|(i, c)| ColorStop {
+                    position: i as f32 / (count - 1).max(1) as f32,
+                    color: *c,
+                })
+                .collect()
+        }
+    };
+
+    return Ok(stops);
+}
+
+/*
+ * GIMP gradient format:
+ * GIMP Gradient
+ * Name: <name>
+ * <segment count>
+ * <left> <middle> <right> <r0> <g0> <b0> <a0> <r1> <g1> <b1> <a1> <blending> <coloring> [...]
+ * Colors are 0-1, alpha is ignored. HSV coloring is treated as RGB.
+ */
+fn parse_ggr(content: &str) -> Result<Vec<GimpSegment>, String> {
+    let mut lines = content.lines();
+
+    if lines.next().map(|l| l.trim()) != Some("GIMP Gradient") {
+        return Err("Missing 'GIMP Gradient' header".to_string());
+    }
+
+    let mut segment_count: Option<usize> = None;
+    let mut segments: Vec<GimpSegment> = Vec::new();
+
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("Name:") { continue; }
+
+        if segment_count.is_none() {
+            let count = line.parse::<usize>()
+                .map_err(|_| format!("Invalid segment count: {}", line))?;
+            segment_count = Some(count);
+            continue;
+        }
+
+        let values: Vec<f32> = line
+            .split_whitespace()
+            .map(|v| v.parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Invalid gradient segment: {}", line))?;
+
+        if values.len() < 13 {
+            return Err(format!("Invalid gradient segment: {}", line));
+        }
+
+        if values[..11].iter().any(|v| !(0.0..=1.0).contains(v)) {
+            return Err(format!("Gradient values must be between 0 and 1: {}", line));
+        }
+
+        if values[0] > values[1] || values[1] > values[2] {
+            return Err(format!("Gradient segment positions must be ascending: {}", line));
+        }
+
+        if let Some(previous) = segments.last() {
+            if values[0] < previous.right - GGR_TOLERANCE {
+                return Err(format!("Gradient segments must be sorted: {}", line));
+            }
+        }
+
+        segments.push(GimpSegment {
+            left: values[0],
+            middle: values[1],
+            right: values[2],
+            color_left: [values[3] * 255.0, values[4] * 255.0, values[5] * 255.0],
+            color_right: [values[7] * 255.0, values[8] * 255.0, values[9] * 255.0],
+            blending: values[11] as u8,
+        });
+    }
+
+    if segments.is_empty() || Some(segments.len()) != segment_count {
+        return Err("Segment count doesn't match".to_string());
+    }
+
+    return Ok(segments);
+}
+
+/*
+ * Positions have to be within 0-1 and colors within 0-255
+ */
+fn check_stops(stops: Vec<ColorStop>) -> Result<Vec<ColorStop>, String> {
+    if stops.is_empty() {
+        return Err("Palette has no colors".to_string());
+    }
+
+    for stop in stops.iter() {
+        if !(0.0..=1.0).contains(&stop.position) {
+            return Err(format!("Position must be between 0 and 1, got {}", stop.position));
+        }
+
+        if stop.color.iter().any(|c| !(0.0..=255.0).contains(c)) {
+            return Err(format!("Color values must be between 0 and 255, got {:?}", stop.color));
+        }
+    }
+
+    return Ok(stops);
+}
+
+fn sample_stops(mut stops: Vec<ColorStop>) -> [[u8; 3]; LUT_SIZE] {
+    let mut lut = [[0x00; 3]; LUT_SIZE];
+    if stops.is_empty() { return lut; }
+
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let t = i as f32 / (LUT_SIZE - 1) as f32;
+
+        let upper = stops.iter().position(|s| s.position >= t).unwrap_or(stops.len() - 1);
+        let lower = upper.saturating_sub(1);
+
+        let a = stops[lower];
+        let b = stops[upper];
+
+        let span = b.position - a.position;
+        let f = if span > 0.0 { ((t - a.position) / span).max(0.0).min(1.0) } else { 1.0 };
+
+        *entry = mix(a.color, b.color, f);
+    }
+
+    return lut;
+}
+
+fn sample_gimp(segments: Vec<GimpSegment>) -> [[u8; 3]; LUT_SIZE] {
+    let mut lut = [[0x00; 3]; LUT_SIZE];
+
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let t = i as f32 / (LUT_SIZE - 1) as f32;
+
+        let segment = segments
+            .iter()
+            .find(|s| t >= s.left && t <= s.right)
+            .unwrap_or(segments.last().unwrap());
+
+        let f = gimp_blend(segment, t);
+        *entry = mix(segment.color_left, segment.color_right, f);
+    }
+
+    return lut;
+}
+
+/*
+ * Same blending functions GIMP uses, the middle point
+ * marks where the colors are mixed 50:50
+ */
+fn gimp_blend(segment: &GimpSegment, t: f32) -> f32 {
+    let length = segment.right - segment.left;
+    if length <= 0.0 { return 0.5; }
+
+    let pos = (t - segment.left) / length;
+    let middle = ((segment.middle - segment.left) / length).max(0.00001).min(0.99999);
+
+    let linear = if pos <= middle {
+        0.5 * pos / middle
+    }
+    else {
+        0.5 + 0.5 * (pos - middle) / (1.0 - middle)
+    };
+
+    return match segment.blending {
+        1 => pos.powf(0.5_f32.ln() / middle.ln()),
+        2 => ((-std::f32::consts::FRAC_PI_2 + std::f32::consts::PI * linear).sin() + 1.0) / 2.0,
+        3 => (1.0 - (linear - 1.0) * (linear - 1.0)).sqrt(),
+        4 => 1.0 - (1.0 - linear * linear).sqrt(),
+        _ => linear,
+    };
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], f: f32) -> [u8; 3] {
+    let mut res = [0x00; 3];
+
+    for c in 0..3 {
+        let value = a[c] + (b[c] - a[c]) * f;
+        res[c] = value.round().max(0.0).min(255.0) as u8;
+    }
+
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_palette_files() {
+        let cases = [
+            // CSV
+            ("valid.csv", "position,r,g,b\n0,0,0,0\n1,255,255,255\n", true),
+            ("evenly_spaced.csv", "0,0,0\n128,0,0\n255,255,0\n", true),
+            ("columns.csv", "0,0,0\n1,2\n", false),
+            ("color_range.csv", "0,0,0,0\n1,256,0,0\n", false),
+            ("position_range.csv", "0,0,0,0\n1.5,255,0,0\n", false),
+            ("negative.csv", "0,-1,0,0\n1,255,0,0\n", false),
+            ("empty.csv", "", false),
+
+            // JSON
+            ("valid.json", r#"{"stops": [{"position": 0.0, "color": [0, 0, 0]}, {"position": 1.0, "color": [255, 0, 0]}]}"#, true),
+            ("colors.json", "[[0, 0, 0], [255, 255, 255]]", true),
+            ("columns.json", "[[0, 0, 0], [255, 255]]", false),
+            ("color_range.json", "[[0, 0, 0], [300, 0, 0]]", false),
+            ("position_range.json", r#"{"stops": [{"position": -0.5, "color": [0, 0, 0]}]}"#, false),
+            ("empty.json", "", false),
+            ("no_colors.json", "[]", false),
+
+            // GIMP gradient
+            ("valid.ggr", "GIMP Gradient\nName: Test\n2\n0 0.25 0.5 0 0 0 1 1 0 0 1 0 0\n0.5 0.75 1 1 0 0 1 1 1 1 1 0 0\n", true),
+            ("columns.ggr", "GIMP Gradient\nName: Test\n1\n0 0.5 1 0 0 0 1 1 1 1\n", false),
+            ("color_range.ggr", "GIMP Gradient\nName: Test\n1\n0 0.5 1 0 0 0 1 2 1 1 1 0 0\n", false),
+            ("unsorted.ggr", "GIMP Gradient\nName: Test\n2\n0.5 0.75 1 1 0 0 1 1 1 1 1 0 0\n0 0.25 0.5 0 0 0 1 1 0 0 1 0 0\n", false),
+            ("middle.ggr", "GIMP Gradient\nName: Test\n1\n0 0.8 0.5 0 0 0 1 1 1 1 1 0 0\n", false),
+            ("count.ggr", "GIMP Gradient\nName: Test\n2\n0 0.5 1 0 0 0 1 1 1 1 1 0 0\n", false),
+            ("empty.ggr", "", false),
+            ("no_segments.ggr", "GIMP Gradient\nName: Test\n", false),
+
+            ("unknown.txt", "0,0,0\n", false),
+            ("no_extension", "0,0,0\n", false),
+        ];
+
+        for (file_name, content, valid) in cases {
+            let res = parse(file_name, content);
+            assert_eq!(res.is_ok(), valid, "{}: {:?}", file_name, res.err());
+        }
+    }
+
+    #[test]
+    fn samples_stops_in_order() {
+        let palette = parse("ramp.csv", "1,255,255,255\n0,0,0,0\n").unwrap();
+
+        assert_eq!(palette.name, "ramp");
+        assert_eq!(palette.sample(0.0, 0.0, 1.0), [0, 0, 0]);
+        assert_eq!(palette.sample(1.0, 0.0, 1.0), [255, 255, 255]);
+        assert_eq!(palette.sample(0.5, 0.0, 1.0), [128, 128, 128]);
+
+        // Outside the range the ends are used
+        assert_eq!(palette.sample(-5.0, 0.0, 1.0), [0, 0, 0]);
+        assert_eq!(palette.sample(5.0, 0.0, 1.0), [255, 255, 255]);
+    }
+
+    #[test]
+    fn samples_gimp_segments() {
+        let palette = parse("red.ggr", "GIMP Gradient\nName: Red\n1\n0 0.5 1 0 0 0 1 1 0 0 1 0 0\n").unwrap();
+
+        assert_eq!(palette.sample(0.0, 0.0, 1.0), [0, 0, 0]);
+        assert_eq!(palette.sample(1.0, 0.0, 1.0), [255, 0, 0]);
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+use rumqttc::{Client, Connection, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::Serialize;
+use serde_json::json;
+
+use super::{live, mlx, Opt};
+use super::server::server_control::{self, RemoteCommands, SettingsChange};
+
+pub const DEFAULT_PORT: u16 = 1883;
+pub const DEFAULT_TOPIC: &str = "thermal-camera";
+pub const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// Messages waiting while the broker is away, newer states are dropped beyond that
+const QUEUE_CAPACITY: usize = 32;
+
+// Retained on the availability topic, the broker sends OFFLINE as last will
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+// Defaults of a Home Assistant binary sensor
+const ALARM_ON: &str = "ON";
+const ALARM_OFF: &str = "OFF";
+
+// Markers announced to Home Assistant, each as "<name>_temp" sensor
+const DISCOVERY_MARKERS: [&str; 1] = [live::SPOT_MARKER];
+
+// Alarms are checked on every frame, this is only how often the thread wakes up without
+const ALARM_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/*
+ * Everything below the base topic, e.g. "thermal-camera/state"
+ */
+#[derive(Debug, Clone)]
+struct Topics {
+    availability: String,
+    state: String,
+    alarm: String,
+    capture_command: String,
+    palette_command: String,
+}
+
+/*
+ * Published on the state topic every interval.
+ * Markers are the temperatures of the markers by name, e.g. the spot set on the touchscreen.
+ */
+#[derive(Serialize)]
+struct State<'a> {
+    timestamp: &'a str,
+    unit: &'a str,
+    min_temp: f32,
+    max_temp: f32,
+    mean_temp: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ambient_temp: Option<f32>,
+    palette: &'a str,
+    markers: BTreeMap<&'a str, f32>,
+}
+
+/*
+ * Published retained on the alarm topic whenever an alarm starts or ends
+ */
+#[derive(Serialize)]
+struct Alarm<'a> {
+    state: &'a str,
+    timestamp: &'a str,
+    unit: &'a str,
+    min_temp: f32,
+    max_temp: f32,
+    below_min: bool,
+    above_max: bool,
+}
+
+impl Topics {
+    fn new(base: &str) -> Self {
+        Self {
+            availability: format!("{}/availability", base),
+            state: format!("{}/state", base),
+            alarm: format!("{}/alarm", base),
+            capture_command: format!("{}/command/capture", base),
+            palette_command: format!("{}/command/palette", base),
+        }
+    }
+}
+
+/*
+ * Connects in the background and keeps reconnecting, frames are handed over by live::publish.
+ * Publishes the state with the marker temperatures every interval and alarms as they happen, takes "capture" and "palette" commands
+ * and announces itself to Home Assistant on every connect.
+ * The unit is the one of the published frames.
+ */
+pub fn start(opt: &Opt, unit: mlx::TemperatureUnits) -> Result<(), String> {
+    if opt.mqtt_topic.is_empty() || opt.mqtt_topic.contains(['+', '#']) {
+        return Err(format!("Invalid MQTT topic: {}", opt.mqtt_topic));
+    }
+
+    let topics = Topics::new(&opt.mqtt_topic);
+    let node_id = get_node_id();
+
+    let mut options = MqttOptions::new(&node_id, &opt.mqtt_host, opt.mqtt_port);
+    options.set_keep_alive(KEEP_ALIVE);
+    options.set_last_will(LastWill::new(&topics.availability, OFFLINE, QoS::AtLeastOnce, true));
+
+    if !opt.mqtt_username.is_empty() {
+        options.set_credentials(&opt.mqtt_username, &opt.mqtt_password);
+    }
+
+    let (client, connection) = Client::new(options, QUEUE_CAPACITY);
+
+    live::enable();
+
+    // Home Assistant is told the unit through the discovery
+    let mut opt = opt.clone();
+    opt.unit = unit;
+
+    let (client_clone, opt_clone, topics_clone) = (client.clone(), opt.clone(), topics.clone());
+    thread::spawn(move || handle_connection(client_clone, connection, opt_clone, topics_clone, node_id));
+
+    if has_alarm(&opt) {
+        let (client_clone, opt_clone, topics_clone) = (client.clone(), opt.clone(), topics.clone());
+        thread::spawn(move || publish_alarms(client_clone, opt_clone, topics_clone));
+    }
+
+    let opt_clone = opt.clone();
+    thread::spawn(move || publish_states(client, opt_clone, topics));
+
+    eprintln!("MQTT publishing to {}:{} under {}", opt.mqtt_host, opt.mqtt_port, opt.mqtt_topic);
+
+    return Ok(());
+}
+
+/*
+ * Unique per sensor, so several cameras can share a broker
+ */
+fn get_node_id() -> String {
+    let serial: String = mlx::get_serial()
+        .unwrap_or("unknown".to_string())
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+
+    return format!("thermal_camera_{}", serial);
+}
+
+/*
+ * Iterating the connection is what keeps it alive, reconnects included
+ */
+fn handle_connection(client: Client, mut connection: Connection, opt: Opt, topics: Topics, node_id: String) {
+    for event in connection.iter() {
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                eprintln!("MQTT connected to {}:{}", opt.mqtt_host, opt.mqtt_port);
+                announce(&client, &opt, &topics, &node_id);
+            },
+
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let payload = String::from_utf8_lossy(&publish.payload).trim().to_string();
+                let topics = topics.clone();
+
+                // Waits for the GUI, which mustn't hold up the connection
+                thread::spawn(move || handle_command(&topics, &publish.topic, &payload));
+            },
+
+            Ok(_) => (),
+
+            Err(err) => {
+                eprintln!("MQTT connection to {}:{} failed: {}", opt.mqtt_host, opt.mqtt_port, err);
+                thread::sleep(RECONNECT_DELAY);
+            },
+        }
+    }
+}
+
+/*
+ * The broker forgets subscriptions of a lost connection, so this runs on every connect
+ */
+fn announce(client: &Client, opt: &Opt, topics: &Topics, node_id: &str) {
+    let mut messages = vec![(topics.availability.clone(), ONLINE.to_string())];
+
+    if !opt.mqtt_discovery_prefix.is_empty() {
+        messages.extend(get_discovery(opt, topics, node_id));
+    }
+
+    for (topic, payload) in messages {
+        client.try_publish(&topic, QoS::AtLeastOnce, true, payload).unwrap_or_else(|err| {
+            eprintln!("MQTT publish to {} failed: {}", topic, err);
+        });
+    }
+
+    for topic in [&topics.capture_command, &topics.palette_command] {
+        client.try_subscribe(topic, QoS::AtLeastOnce).unwrap_or_else(|err| {
+            eprintln!("MQTT subscribe to {} failed: {}", topic, err);
+        });
+    }
+}
+
+/*
+ * Home Assistant discovery: sensors for the temperatures and markers,
+ * a button to capture and a select for the palette, all of one device.
+ * A marker sensor is unknown while its marker isn't set.
+ */
+fn get_discovery(opt: &Opt, topics: &Topics, node_id: &str) -> Vec<(String, String)> {
+    let prefix = &opt.mqtt_discovery_prefix;
+
+    let device = json!({
+        "identifiers": [node_id],
+        "name": "Thermal Camera",
+        "model": "MLX90640",
+        "serial_number": mlx::get_serial().unwrap_or_default(),
+    });
+
+    let mut sensors: Vec<(String, String, String)> = [
+        ("min_temp", "Min temperature"),
+        ("max_temp", "Max temperature"),
+        ("mean_temp", "Mean temperature"),
+        ("ambient_temp", "Ambient temperature"),
+    ].iter().map(|(key, name)| {
+        (key.to_string(), name.to_string(), format!("{{{{ value_json.{} }}}}", key))
+    }).collect();
+
+    for marker in DISCOVERY_MARKERS {
+        sensors.push((
+            format!("{}_temp", marker.to_lowercase()),
+            format!("{} temperature", marker),
+            format!("{{{{ value_json.markers.get('{}') }}}}", marker),
+        ));
+    }
+
+    let mut res: Vec<(String, String)> = sensors.iter().map(|(key, name, template)| {
+        let config = json!({
+            "name": name,
+            "unique_id": format!("{}_{}", node_id, key),
+            "state_topic": topics.state,
+            "value_template": template,
+            "unit_of_measurement": opt.unit.symbol(),
+            "device_class": "temperature",
+            "state_class": "measurement",
+            "availability_topic": topics.availability,
+            "device": device,
+        });
+
+        (format!("{}/sensor/{}/{}/config", prefix, node_id, key), config.to_string())
+    }).collect();
+
+    let capture = json!({
+        "name": "Capture",
+        "unique_id": format!("{}_capture", node_id),
+        "command_topic": topics.capture_command,
+        "availability_topic": topics.availability,
+        "device": device,
+    });
+
+    res.push((format!("{}/button/{}/capture/config", prefix, node_id), capture.to_string()));
+
+    let palettes: Vec<String> = mlx::BUILTIN_COLOR_TYPES
+        .into_iter()
+        .chain(mlx::get_custom_palettes())
+        .map(|p| p.to_string())
+        .collect();
+
+    let palette = json!({
+        "name": "Palette",
+        "unique_id": format!("{}_palette", node_id),
+        "command_topic": topics.palette_command,
+        "state_topic": topics.state,
+        "value_template": "{{ value_json.palette }}",
+        "options": palettes,
+        "availability_topic": topics.availability,
+        "device": device,
+    });
+
+    res.push((format!("{}/select/{}/palette/config", prefix, node_id), palette.to_string()));
+
+    let alarm = json!({
+        "name": "Temperature alarm",
+        "unique_id": format!("{}_alarm", node_id),
+        "state_topic": topics.alarm,
+        "value_template": "{{ value_json.state }}",
+        "device_class": "problem",
+        "availability_topic": topics.availability,
+        "device": device,
+    });
+
+    // An empty config removes the entity again once the alarm is turned off
+    let alarm_config = if has_alarm(opt) { alarm.to_string() } else { "".to_string() };
+    res.push((format!("{}/binary_sensor/{}/alarm/config", prefix, node_id), alarm_config));
+
+    return res;
+}
+
+/*
+ * Goes through the same path as the HTTP API, so the GUI shows the change
+ */
+fn handle_command(topics: &Topics, topic: &str, payload: &str) {
+    let command = if topic == topics.capture_command {
+        RemoteCommands::Save
+    }
+    else if topic == topics.palette_command {
+        match payload.parse() {
+            Ok(color_type) => RemoteCommands::ChangeSettings(SettingsChange {
+                color_type: Some(color_type),
+                ..Default::default()
+            }),
+
+            Err(err) => {
+                eprintln!("MQTT palette {}: {}", payload, err);
+                return;
+            },
+        }
+    }
+    else {
+        return;
+    };
+
+    match server_control::send_command(command).and_then(|res| res) {
+        Ok(_) => eprintln!("MQTT command {} done", topic),
+        Err(err) => eprintln!("MQTT command {} failed: {}", topic, err),
+    }
+}
+
+/*
+ * Skips an interval when no new frame arrived, e.g. while the sensor fails
+ */
+fn publish_states(client: Client, opt: Opt, topics: Topics) {
+    let interval = Duration::from_secs(opt.mqtt_interval as u64);
+    let mut last_seq = None;
+
+    loop {
+        if let Some(frame) = live::get_latest_frame().filter(|f| Some(f.seq) != last_seq) {
+            last_seq = Some(frame.seq);
+
+            let unit = mlx::TemperatureUnits::from_symbol(&frame.metadata.unit).unwrap_or(mlx::TemperatureUnits::Celsius);
+
+            let ambient_temp = match mlx::read_ambient_temperature() {
+                Ok(temp) => Some(unit.from_celsius(temp)),
+                Err(err) => {
+                    eprintln!("Ambient temperature read failed: {}", err);
+                    None
+                },
+            };
+
+            let state = State {
+                timestamp: &frame.metadata.timestamp,
+                unit: &frame.metadata.unit,
+                min_temp: frame.metadata.min_temp,
+                max_temp: frame.metadata.max_temp,
+                mean_temp: frame.mean_temp,
+                ambient_temp,
+                palette: &frame.metadata.color_type,
+                markers: frame.metadata.markers.iter().map(|m| (m.name.as_str(), m.temperature)).collect(),
+            };
+
+            let payload = serde_json::to_string(&state).unwrap_or_default();
+
+            client.try_publish(&topics.state, QoS::AtMostOnce, false, payload).unwrap_or_else(|err| {
+                eprintln!("MQTT publish to {} failed: {}", topics.state, err);
+            });
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+fn has_alarm(opt: &Opt) -> bool {
+    return opt.mqtt_alarm_min.is_some() || opt.mqtt_alarm_max.is_some();
+}
+
+/*
+ * Checks every frame, but only publishes when the alarm starts or ends.
+ * Retained, so Home Assistant knows the current state after a restart.
+ */
+fn publish_alarms(client: Client, opt: Opt, topics: Topics) {
+    let mut seq = None;
+    let mut last_alarm = None;
+
+    loop {
+        let frame = match live::wait_for_frame(seq, ALARM_WAIT_TIMEOUT) {
+            Some(frame) => frame,
+            None => continue,
+        };
+
+        seq = Some(frame.seq);
+
+        let below_min = opt.mqtt_alarm_min.map(|min| frame.metadata.min_temp < min).unwrap_or(false);
+        let above_max = opt.mqtt_alarm_max.map(|max| frame.metadata.max_temp > max).unwrap_or(false);
+        let alarm = below_min || above_max;
+
+        if last_alarm == Some(alarm) { continue; }
+
+        let payload = serde_json::to_string(&Alarm {
+            state: if alarm { ALARM_ON } else { ALARM_OFF },
+            timestamp: &frame.metadata.timestamp,
+            unit: &frame.metadata.unit,
+            min_temp: frame.metadata.min_temp,
+            max_temp: frame.metadata.max_temp,
+            below_min,
+            above_max,
+        }).unwrap_or_default();
+
+        // Tried again with the next frame if the queue is full
+        match client.try_publish(&topics.alarm, QoS::AtLeastOnce, true, payload) {
+            Ok(()) => last_alarm = Some(alarm),
+            Err(err) => eprintln!("MQTT publish to {} failed: {}", topics.alarm, err),
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::io::{self, Cursor, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use image::ImageEncoder;
+use serde::Serialize;
+use tiny_http::{Header, Request, Response};
+
+use super::{bsp, live, mlx, mqtt, Opt};
+use bsp::bsp_export::FrameMetadata;
+
+pub mod server_control;
+pub mod server_tcp;
+
+pub const DEFAULT_PORT: u16 = 8080;
+
+const JPEG_QUALITY: u8 = 85;
+
+// Every stream keeps a thread busy, so their number is limited
+const MAX_STREAMS: usize = 8;
+
+// Requests are handled by a fixed number of threads, some are always left for other than streams
+const WORKER_COUNT: usize = MAX_STREAMS + 4;
+
+// A client not taking any data for this long is dropped, so it can't hold a thread forever
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Streams look for a closed connection at least this often
+const FRAME_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Gives up once the sensor failed this many frames in a row
+const MAX_CONSECUTIVE_ERRORS: u32 = 10;
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+const BOUNDARY: &str = "frame";
+
+const VIEWER_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Thermal Camera</title>
+<style>
+body { background: #000; color: #fff; font-family: sans-serif; text-align: center; }
+img { width: 100%; max-width: 960px; image-rendering: pixelated; }
+span { display: inline-block; margin: 0 1em; }
+</style>
+</head>
+<body>
+<img src="/stream.mjpg?token={token}">
+<p><span id="min"></span><span id="max"></span><span id="mean"></span></p>
+<p><a href="/snapshot.png?token={token}">Snapshot</a> <a href="/frame.json?token={token}">JSON</a></p>
+<script>
+async function update() {
+    try {
+        const frame = await (await fetch("/frame.json?token={token}")).json();
+        document.getElementById("min").textContent = "Min " + frame.min_temp.toFixed(1) + " " + frame.unit;
+        document.getElementById("max").textContent = "Max " + frame.max_temp.toFixed(1) + " " + frame.unit;
+        document.getElementById("mean").textContent = "Mean " + frame.mean_temp.toFixed(1) + " " + frame.unit;
+    } catch (err) {}
+}
+update();
+setInterval(update, 1000);
+</script>
+</body>
+</html>
+"#;
+
+#[derive(Serialize)]
+struct FrameJson<'a> {
+    #[serde(flatten)]
+    metadata: &'a FrameMetadata,
+    mean_temp: f32,
+    temperature_grid: &'a [f32],
+}
+
+static STREAMS: AtomicUsize = AtomicUsize::new(0);
+
+/*
+ * Starts serving in the background, frames are handed over by live::publish.
+ * Endpoints: / (viewer), /stream.mjpg, /frame.json, /snapshot.png and the /api/ of server_control,
+ * each needs the token as "?token=" or "Authorization: Bearer" header.
+ */
+pub fn start(opt: &Opt) -> Result<SocketAddr, String> {
+    if opt.http_token.is_empty() {
+        return Err("HTTP server needs a token, set network.http_token or THERMAL_CAMERA_NETWORK_HTTP_TOKEN".to_string());
+    }
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], opt.http_port));
+    let listener = TcpListener::bind(addr)
+        .and_then(|listener| set_write_timeout(&listener, WRITE_TIMEOUT).map(|_| listener))
+        .map_err(|err| format!("Failed to start HTTP server on port {}: {}", opt.http_port, err))?;
+
+    let server = tiny_http::Server::from_listener(listener, None)
+        .map_err(|err| format!("Failed to start HTTP server on port {}: {}", opt.http_port, err))?;
+
+    let server = Arc::new(server);
+
+    live::enable();
+
+    for _ in 0..WORKER_COUNT {
+        let (server, token) = (server.clone(), opt.http_token.clone());
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle_request(request, &token);
+            }
+        });
+    }
+
+    eprintln!("HTTP server listening on port {}", opt.http_port);
+
+    return Ok(addr);
+}
+
+/*
+ * The network services without GUI, the sensor is read here and the
+ * remote commands are run in between frames. Ends once the sensor failed for good.
+ */
+pub fn run(opt: &Opt, config_path: &Path) -> Result<(), String> {
+    if !opt.http_enabled && !opt.tcp_enabled && !opt.mqtt_enabled {
+        return Err("Nothing to serve, enable the HTTP server (--http), TCP stream (--tcp) and/or MQTT (--mqtt)".to_string());
+    }
+
+    if opt.http_enabled {
+        start(opt)?;
+    }
+
+    if opt.tcp_enabled {
+        server_tcp::start(opt)?;
+    }
+
+    if opt.mqtt_enabled {
+        mqtt::start(opt, opt.unit)?;
+    }
+
+    let remote_rx = server_control::take_control(|| ());
+    let mut opt = opt.clone();
+    let mut errors = 0;
+
+    loop {
+        let image = match mlx::take_image(&opt.color_settings(), opt.unit) {
+            Ok(image) => {
+                errors = 0;
+                image
+            },
+
+            Err(err) => {
+                errors += 1;
+                eprintln!("Sensor read failed ({}/{}): {}", errors, MAX_CONSECUTIVE_ERRORS, err);
+
+                if errors >= MAX_CONSECUTIVE_ERRORS { return Err(err); }
+
+                thread::sleep(RETRY_DELAY);
+                continue;
+            },
+        };
+
+        live::publish(&opt, &image, opt.unit);
+
+        for request in remote_rx.try_iter() {
+            let res = server_control::run_headless(&request.command, &mut opt, &image, config_path);
+            request.reply(res);
+        }
+    }
+}
+
+fn handle_request(request: Request, token: &str) {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let path = path.to_string();
+
+    if !is_authorized(&request, query, token) {
+        respond_text(request, 401, "Missing or wrong token");
+        return;
+    }
+
+    match path.as_str() {
+        "/" | "/index.html" => {
+            let html = VIEWER_HTML.replace("{token}", token);
+            respond(request, 200, "text/html; charset=utf-8", html.into_bytes());
+        },
+
+        "/frame.json" => match live::get_latest_frame() {
+            Some(frame) => respond(request, 200, "application/json", frame_to_json(&frame)),
+            None => respond_text(request, 503, "No frame yet"),
+        },
+
+        "/snapshot.png" => match live::get_latest_frame().map(|frame| encode_png(&frame)) {
+            Some(Ok(png)) => respond(request, 200, "image/png", png),
+            Some(Err(err)) => respond_text(request, 500, &err),
+            None => respond_text(request, 503, "No frame yet"),
+        },
+
+        "/stream.mjpg" => {
+            if STREAMS.fetch_add(1, Ordering::SeqCst) >= MAX_STREAMS {
+                STREAMS.fetch_sub(1, Ordering::SeqCst);
+                respond_text(request, 503, "Too many streams");
+                return;
+            }
+
+            // Ends when the client goes away
+            stream_mjpeg(request).unwrap_or_default();
+            STREAMS.fetch_sub(1, Ordering::SeqCst);
+        },
+
+        path if path.starts_with("/api/") => server_control::handle(request, path),
+
+        _ => respond_text(request, 404, "Not found"),
+    }
+}
+
+/*
+ * tiny_http doesn't hand out the sockets, but on Linux they take over
+ * the timeout of the listener they were accepted from
+ */
+fn set_write_timeout(listener: &TcpListener, timeout: Duration) -> io::Result<()> {
+    let timeval = libc::timeval {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+    };
+
+    let result = unsafe {
+        libc::setsockopt(
+            listener.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_SNDTIMEO,
+            &timeval as *const libc::timeval as *const libc::c_void,
+            std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    return Ok(());
+}
+
+fn is_authorized(request: &Request, query: &str, token: &str) -> bool {
+    let query_token = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("token="));
+
+    let header_token = request.headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "));
+
+    return query_token.or(header_token)
+        .map(|t| constant_time_eq(t.as_bytes(), token.as_bytes()))
+        .unwrap_or(false);
+}
+
+/*
+ * Takes as long for a wrong token as for a right one, so it can't be guessed byte by byte
+ */
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() { return false; }
+
+    return a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0;
+}
+
+/*
+ * multipart/x-mixed-replace, every part replaces the previous picture
+ */
+fn stream_mjpeg(request: Request) -> io::Result<()> {
+    let mut out = request.into_writer();
+
+    write!(
+        out,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+    out.flush()?;
+
+    let mut seq = None;
+
+    loop {
+        // Nothing new, the last frame is sent again to find out if the client is still there
+        let frame = match live::wait_for_frame(seq, FRAME_WAIT_TIMEOUT).or_else(live::get_latest_frame) {
+            Some(frame) => frame,
+            None => continue,
+        };
+
+        seq = Some(frame.seq);
+
+        let jpeg = encode_jpeg(&frame).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+        write!(out, "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", BOUNDARY, jpeg.len())?;
+        out.write_all(&jpeg)?;
+        out.write_all(b"\r\n")?;
+        out.flush()?;
+    }
+}
+
+fn frame_to_json(frame: &live::Frame) -> Vec<u8> {
+    let json = FrameJson {
+        metadata: &frame.metadata,
+        mean_temp: frame.mean_temp,
+        temperature_grid: &frame.temperature_grid,
+    };
+
+    return serde_json::to_vec(&json).unwrap_or_default();
+}
+
+fn encode_jpeg(frame: &live::Frame) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY)
+        .encode(&frame.pixels, frame.width as u32, frame.height as u32, image::ColorType::Rgb8)
+        .map_err(|err| err.to_string())?;
+
+    return Ok(buf);
+}
+
+fn encode_png(frame: &live::Frame) -> Result<Vec<u8>, String> {
+    let mut buf = Cursor::new(Vec::new());
+
+    image::codecs::png::PngEncoder::new(&mut buf)
+        .write_image(&frame.pixels, frame.width as u32, frame.height as u32, image::ColorType::Rgb8)
+        .map_err(|err| err.to_string())?;
+
+    return Ok(buf.into_inner());
+}
+
+fn respond(request: Request, status: u16, content_type: &str, body: Vec<u8>) {
+    let header = Header::from_bytes("Content-Type", content_type).unwrap();
+    let response = Response::from_data(body)
+        .with_status_code(status)
+        .with_header(header);
+
+    // The client may be gone already, nothing to do about it
+    request.respond(response).unwrap_or_default();
+}
+
+fn respond_text(request: Request, status: u16, text: &str) {
+    respond(request, status, "text/plain; charset=utf-8", text.as_bytes().to_vec());
+}
@@ -0,0 +1,316 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Method, Request};
+
+use std::path::Path;
+
+use super::super::{bsp, mlx, Opt};
+use super::respond;
+
+// The GUI answers within a frame, unless it hangs
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Answered with 405 for any other method than the one listed at handle()
+const API_PATHS: [&str; 6] = [
+    "/api/settings",
+    "/api/save",
+    "/api/recording/start",
+    "/api/recording/stop",
+    "/api/freeze",
+    "/api/unfreeze",
+];
+
+/*
+ * What the GUI is asked to do, it replies with the settings afterwards
+ */
+#[derive(Debug)]
+pub enum RemoteCommands {
+    GetSettings,
+    ChangeSettings(SettingsChange),
+    Save,
+    StartRecording,
+    StopRecording,
+    Freeze(bool),
+}
+
+/*
+ * Only the given values are changed
+ */
+#[derive(Debug, Default)]
+pub struct SettingsChange {
+    pub framerate: Option<mlx::Framerates>,
+    pub color_type: Option<mlx::ColorTypes>,
+    pub range_mode: Option<mlx::RangeModes>,
+    pub range_min: Option<f32>,
+    pub range_max: Option<f32>,
+    pub emissivity: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemoteSettings {
+    pub framerate: String,
+    pub palette: String,
+    pub range_mode: String,
+    pub range_min: f32,
+    pub range_max: f32,
+    pub emissivity: f32,
+    pub frozen: bool,
+    pub recording: bool,
+}
+
+pub struct RemoteRequest {
+    pub command: RemoteCommands,
+    reply_tx: Sender<Result<RemoteSettings, String>>,
+}
+
+/*
+ * Request body of PUT /api/settings, framerate as number or text
+ */
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsJson {
+    framerate: Option<serde_json::Value>,
+    palette: Option<String>,
+    range_mode: Option<String>,
+    range_min: Option<f32>,
+    range_max: Option<f32>,
+    emissivity: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct ErrorJson {
+    error: String,
+}
+
+lazy_static! {
+    static ref CONTROL: Mutex<Option<(Sender<RemoteRequest>, Box<dyn Fn() + Send>)>> = Mutex::new(None);
+}
+
+impl SettingsChange {
+    /*
+     * Checks everything first, so an invalid change leaves the settings as they are.
+     * Only what is changed is checked, e.g. the range not when setting the palette.
+     */
+    pub fn apply(&self, opt: &mut Opt) -> Result<(), String> {
+        let range_min = self.range_min.unwrap_or(opt.range_min);
+        let range_max = self.range_max.unwrap_or(opt.range_max);
+
+        if (self.range_min.is_some() || self.range_max.is_some()) && range_min >= range_max {
+            return Err(format!("Range minimum {} has to be below the maximum {}", range_min, range_max));
+        }
+
+        if let Some(framerate) = self.framerate {
+            opt.framerate = framerate;
+            mlx::set_framerate(framerate);
+        }
+
+        if let Some(color_type) = self.color_type {
+            opt.color_type = color_type;
+        }
+
+        if let Some(range_mode) = self.range_mode {
+            opt.range_mode = range_mode;
+        }
+
+        // A range alone switches to manual, like the range buttons
+        if self.range_min.is_some() || self.range_max.is_some() {
+            if self.range_mode.is_none() {
+                opt.range_mode = mlx::RangeModes::Manual;
+            }
+
+            opt.range_min = range_min;
+            opt.range_max = range_max;
+        }
+
+        if let Some(emissivity) = self.emissivity {
+            opt.emissivity = emissivity.clamp(mlx::MIN_EMISSIVITY, 1.0);
+            mlx::set_emissivity(opt.emissivity);
+        }
+
+        return Ok(());
+    }
+}
+
+impl RemoteSettings {
+    pub fn new(opt: &Opt, frozen: bool, recording: bool) -> Self {
+        Self {
+            framerate: opt.framerate.to_string(),
+            palette: opt.color_type.to_string(),
+            range_mode: opt.range_mode.to_string(),
+            range_min: opt.range_min,
+            range_max: opt.range_max,
+            emissivity: opt.emissivity,
+            frozen,
+            recording,
+        }
+    }
+}
+
+impl RemoteRequest {
+    pub fn reply(self, res: Result<RemoteSettings, String>) {
+        // The HTTP client may have given up already
+        self.reply_tx.send(res).unwrap_or_default();
+    }
+}
+
+/*
+ * Hands remote commands to whoever takes the receiver, the GUI or the headless loop.
+ * wake is called for every command, the GUI only looks for them when it redraws.
+ */
+pub fn take_control(wake: impl Fn() + Send + 'static) -> Receiver<RemoteRequest> {
+    let (tx, rx) = mpsc::channel();
+    CONTROL.lock().unwrap().replace((tx, Box::new(wake)));
+
+    return rx;
+}
+
+/*
+ * GET  /api/settings
+ * PUT  /api/settings            {"framerate", "palette", "range_mode", "range_min", "range_max", "emissivity"}
+ * POST /api/save
+ * POST /api/recording/start
+ * POST /api/recording/stop
+ * POST /api/freeze
+ * POST /api/unfreeze
+ */
+pub fn handle(mut request: Request, path: &str) {
+    let method = request.method().clone();
+
+    let command = match (&method, path) {
+        (Method::Get, "/api/settings") => Ok(RemoteCommands::GetSettings),
+        (Method::Put, "/api/settings") => read_settings_change(&mut request),
+        (Method::Post, "/api/save") => Ok(RemoteCommands::Save),
+        (Method::Post, "/api/recording/start") => Ok(RemoteCommands::StartRecording),
+        (Method::Post, "/api/recording/stop") => Ok(RemoteCommands::StopRecording),
+        (Method::Post, "/api/freeze") => Ok(RemoteCommands::Freeze(true)),
+        (Method::Post, "/api/unfreeze") => Ok(RemoteCommands::Freeze(false)),
+
+        (_, path) if API_PATHS.contains(&path) => {
+            respond_error(request, 405, &format!("{} isn't allowed for {}", method, path));
+            return;
+        },
+
+        _ => {
+            respond_error(request, 404, "Not found");
+            return;
+        },
+    };
+
+    let command = match command {
+        Ok(command) => command,
+        Err(err) => {
+            respond_error(request, 400, &err);
+            return;
+        },
+    };
+
+    match send_command(command) {
+        Ok(Ok(settings)) => respond(request, 200, "application/json", serde_json::to_vec(&settings).unwrap_or_default()),
+        Ok(Err(err)) => respond_error(request, 409, &err),
+        Err(err) => respond_error(request, 503, &err),
+    }
+}
+
+/*
+ * Without GUI only settings and saving are available, the settings are saved like the GUI does
+ */
+pub fn run_headless(command: &RemoteCommands, opt: &mut Opt, image: &mlx::ImageRead, config_path: &Path) -> Result<RemoteSettings, String> {
+    match command {
+        RemoteCommands::GetSettings => (),
+
+        RemoteCommands::ChangeSettings(change) => {
+            let old = opt.clone();
+            change.apply(opt)?;
+
+            bsp::bsp_config::update_config(config_path, &old, opt).unwrap_or_else(|err| {
+                eprintln!("Failed to write {}: {}", config_path.display(), err);
+            });
+        },
+
+        RemoteCommands::Save => {
+            let path = bsp::bsp_export::save_capture(opt, image).map_err(|err| err.to_string())?;
+            eprintln!("Saved {}", path);
+        },
+
+        _ => return Err("Only available with the GUI".to_string()),
+    }
+
+    return Ok(RemoteSettings::new(opt, false, false));
+}
+
+/*
+ * Outer error if the GUI didn't answer, inner error if the command failed
+ */
+pub fn send_command(command: RemoteCommands) -> Result<Result<RemoteSettings, String>, String> {
+    let (reply_tx, reply_rx) = mpsc::channel();
+
+    {
+        let control = CONTROL.lock().unwrap();
+        let (tx, wake) = control.as_ref().ok_or("Nothing to control, neither GUI nor serve running".to_string())?;
+
+        tx.send(RemoteRequest { command, reply_tx }).map_err(|_| "GUI closed".to_string())?;
+        wake();
+    }
+
+    return reply_rx.recv_timeout(REPLY_TIMEOUT).map_err(|_| "GUI didn't answer".to_string());
+}
+
+fn read_settings_change(request: &mut Request) -> Result<RemoteCommands, String> {
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body).map_err(|err| err.to_string())?;
+
+    let json: SettingsJson = serde_json::from_str(&body).map_err(|err| format!("Invalid settings: {}", err))?;
+
+    let framerate = match json.framerate {
+        Some(serde_json::Value::String(s)) => Some(s),
+        Some(serde_json::Value::Number(n)) => n.as_f64().map(|f| f.to_string()),
+        Some(_) => return Err("Invalid framerate".to_string()),
+        None => None,
+    };
+
+    let change = SettingsChange {
+        framerate: framerate.map(|f| f.parse()).transpose()?,
+        color_type: json.palette.map(|p| p.parse()).transpose()?,
+        range_mode: json.range_mode.map(|m| m.parse()).transpose()?,
+        range_min: json.range_min,
+        range_max: json.range_max,
+        emissivity: json.emissivity,
+    };
+
+    return Ok(RemoteCommands::ChangeSettings(change));
+}
+
+fn respond_error(request: Request, status: u16, err: &str) {
+    let json = ErrorJson { error: err.to_string() };
+    respond(request, status, "application/json", serde_json::to_vec(&json).unwrap_or_default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_only_the_changed_settings() {
+        let mut opt = Opt::default();
+        opt.range_min = 40.0;
+        opt.range_max = 20.0;
+
+        // The range isn't touched, so it isn't checked either
+        let change = SettingsChange { color_type: Some(mlx::ColorTypes::Gray), ..Default::default() };
+        assert!(change.apply(&mut opt).is_ok());
+        assert!(matches!(opt.color_type, mlx::ColorTypes::Gray));
+
+        let change = SettingsChange { range_max: Some(30.0), color_type: Some(mlx::ColorTypes::Hue), ..Default::default() };
+        assert!(change.apply(&mut opt).is_err());
+        assert!(matches!(opt.color_type, mlx::ColorTypes::Gray));
+        assert_eq!(opt.range_max, 20.0);
+
+        let change = SettingsChange { range_max: Some(50.0), ..Default::default() };
+        assert!(change.apply(&mut opt).is_ok());
+        assert_eq!((opt.range_min, opt.range_max), (40.0, 50.0));
+        assert_eq!(opt.range_mode, mlx::RangeModes::Manual);
+    }
+}
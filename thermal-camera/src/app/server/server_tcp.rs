@@ -0,0 +1,251 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use super::super::{live, mlx, Opt};
+use super::{constant_time_eq, FRAME_WAIT_TIMEOUT, WRITE_TIMEOUT};
+
+pub const DEFAULT_PORT: u16 = 8081;
+
+/*
+ * Raw temperatures over plain TCP, all numbers little endian.
+ *
+ * Handshake: the client sends the HTTP token and a newline,
+ * the server answers "TCAM", version (u8) and status (u8, 0 ok, 1 wrong token, 2 too many clients).
+ * Unless ok the connection is closed.
+ *
+ * Then one frame after another:
+ *   0   4  "TCAM"
+ *   4   1  version
+ *   5   1  unit: 0 Celsius, 1 Fahrenheit, 2 Kelvin
+ *   6   2  header length in bytes, the grid starts there
+ *   8   2  width
+ *   10  2  height
+ *   12  4  frame counter (u32), a gap means the client was too slow for every frame
+ *   16  8  timestamp, unix seconds (f64)
+ *   24  4  min temperature (f32)
+ *   28  4  max temperature (f32)
+ *   32  4  mean temperature (f32)
+ *   36     width * height temperatures (f32), row by row
+ *
+ * A newer minor change only appends to the header, so readers skip up to the header length.
+ * The version is raised when existing fields change.
+ *
+ * Frames aren't queued per client. One that reads slower than the sensor delivers
+ * gets the newest frame whenever it's ready for the next, the ones in between are skipped
+ * and only show as gap in the frame counter. Without new frames the last one
+ * is sent again every 5 seconds, with the same counter.
+ */
+pub const MAGIC: &[u8; 4] = b"TCAM";
+pub const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 36;
+
+pub const STATUS_OK: u8 = 0;
+pub const STATUS_WRONG_TOKEN: u8 = 1;
+pub const STATUS_BUSY: u8 = 2;
+
+// Longer lines aren't a token
+pub const MAX_TOKEN_LEN: usize = 256;
+
+// Every client keeps a thread busy, so their number is limited
+const MAX_CLIENTS: usize = 8;
+
+// Connections still sending the token, counted separately so they can't keep clients out
+const MAX_HANDSHAKES: usize = 8;
+
+// Time for the client to send the token
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static CLIENTS: AtomicUsize = AtomicUsize::new(0);
+static HANDSHAKES: AtomicUsize = AtomicUsize::new(0);
+
+/*
+ * One of a limited number of places, given back when dropped
+ */
+struct Slot(&'static AtomicUsize);
+
+/*
+ * Starts listening in the background, frames are handed over by live::publish.
+ * Uses the token of the HTTP server.
+ */
+pub fn start(opt: &Opt) -> Result<SocketAddr, String> {
+    if opt.http_token.is_empty() {
+        return Err("TCP stream needs a token, set network.http_token or THERMAL_CAMERA_NETWORK_HTTP_TOKEN".to_string());
+    }
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], opt.tcp_port));
+    let listener = TcpListener::bind(addr)
+        .map_err(|err| format!("Failed to start TCP stream on port {}: {}", opt.tcp_port, err))?;
+
+    let token = opt.http_token.clone();
+
+    live::enable();
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handshake = match Slot::take(&HANDSHAKES, MAX_HANDSHAKES) {
+                Some(slot) => slot,
+                None => continue,
+            };
+
+            let token = token.clone();
+
+            // Ends when the client goes away
+            thread::spawn(move || handle_client(stream, &token, handshake).unwrap_or_default());
+        }
+    });
+
+    eprintln!("TCP frame stream listening on port {}", opt.tcp_port);
+
+    return Ok(addr);
+}
+
+/*
+ * A client slot is only taken once the token is right
+ */
+fn handle_client(stream: TcpStream, token: &str, handshake: Slot) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+    // A client that stops reading would otherwise block its thread forever
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(stream.try_clone()?)
+        .take(MAX_TOKEN_LEN as u64 + 1)
+        .read_line(&mut line)?;
+
+    let mut out = BufWriter::new(stream);
+
+    let authorized = constant_time_eq(line.trim_end().as_bytes(), token.as_bytes());
+    let client = if authorized { Slot::take(&CLIENTS, MAX_CLIENTS) } else { None };
+
+    drop(handshake);
+
+    let status = match (authorized, &client) {
+        (false, _) => STATUS_WRONG_TOKEN,
+        (true, None) => STATUS_BUSY,
+        (true, Some(_)) => STATUS_OK,
+    };
+
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION, status])?;
+    out.flush()?;
+
+    if status != STATUS_OK { return Ok(()); }
+
+    let mut seq = None;
+
+    loop {
+        // Nothing new, the last frame is sent again to find out if the client is still there
+        let frame = match live::wait_for_frame(seq, FRAME_WAIT_TIMEOUT).or_else(live::get_latest_frame) {
+            Some(frame) => frame,
+
+            // No frame yet, nothing to write that would fail
+            None if is_closed(out.get_ref())? => return Ok(()),
+            None => continue,
+        };
+
+        seq = Some(frame.seq);
+
+        write_frame(&mut out, &frame)?;
+        out.flush()?;
+    }
+}
+
+/*
+ * The client sends nothing after the token, so reading the end of the stream means it's gone
+ */
+fn is_closed(stream: &TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let res = stream.peek(&mut [0u8; 1]);
+    stream.set_nonblocking(false)?;
+
+    return match res {
+        Ok(0) => Ok(true),
+        Ok(_) => Ok(false),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err),
+    };
+}
+
+impl Slot {
+    fn take(counter: &'static AtomicUsize, max: usize) -> Option<Self> {
+        if counter.fetch_add(1, Ordering::SeqCst) >= max {
+            counter.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        return Some(Slot(counter));
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn unit_code(unit: mlx::TemperatureUnits) -> u8 {
+    match unit {
+        mlx::TemperatureUnits::Celsius => 0,
+        mlx::TemperatureUnits::Fahrenheit => 1,
+        mlx::TemperatureUnits::Kelvin => 2,
+    }
+}
+
+pub fn unit_from_code(code: u8) -> Option<mlx::TemperatureUnits> {
+    match code {
+        0 => Some(mlx::TemperatureUnits::Celsius),
+        1 => Some(mlx::TemperatureUnits::Fahrenheit),
+        2 => Some(mlx::TemperatureUnits::Kelvin),
+        _ => None,
+    }
+}
+
+pub fn write_frame(out: &mut impl Write, frame: &live::Frame) -> io::Result<()> {
+    let unit = mlx::TemperatureUnits::from_symbol(&frame.metadata.unit).unwrap_or(mlx::TemperatureUnits::Celsius);
+
+    let timestamp = chrono::DateTime::parse_from_rfc3339(&frame.metadata.timestamp)
+        .map(|t| t.timestamp_micros() as f64 / 1_000_000.0)
+        .unwrap_or(0.0);
+
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION, unit_code(unit)])?;
+    out.write_all(&(HEADER_LEN as u16).to_le_bytes())?;
+    out.write_all(&(frame.metadata.width as u16).to_le_bytes())?;
+    out.write_all(&(frame.metadata.height as u16).to_le_bytes())?;
+    out.write_all(&(frame.seq as u32).to_le_bytes())?;
+    out.write_all(&timestamp.to_le_bytes())?;
+
+    for value in [frame.metadata.min_temp, frame.metadata.max_temp, frame.mean_temp] {
+        out.write_all(&value.to_le_bytes())?;
+    }
+
+    for t in frame.temperature_grid.iter() {
+        out.write_all(&t.to_le_bytes())?;
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_closed_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        assert!(!is_closed(&stream).unwrap());
+
+        drop(client);
+        thread::sleep(Duration::from_millis(100));
+
+        assert!(is_closed(&stream).unwrap());
+    }
+}
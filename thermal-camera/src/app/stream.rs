@@ -0,0 +1,226 @@
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+use super::{mlx, Opt};
+
+// Gives up once the sensor failed this many frames in a row
+const MAX_CONSECUTIVE_ERRORS: u32 = 10;
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/*
+ * Jsonl:  one JSON object per line
+ * Csv:    header line, then one line per frame
+ * Binary: little endian, per frame an f64 unix timestamp in seconds,
+ *         f32 ambient, min, max and mean temperature,
+ *         then 768 f32 temperatures row by row if the grid is enabled
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamFormats {
+    Jsonl,
+    Csv,
+    Binary,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: String,
+    unit: &'a str,
+    ambient_temp: f32,
+    min_temp: f32,
+    max_temp: f32,
+    mean_temp: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature_grid: Option<&'a [f32]>,
+}
+
+impl ToString for StreamFormats {
+    fn to_string(&self) -> String {
+        match self {
+            StreamFormats::Jsonl => "JSON Lines".to_string(),
+            StreamFormats::Csv => "CSV".to_string(),
+            StreamFormats::Binary => "Binary".to_string(),
+        }
+    }
+}
+
+impl FromStr for StreamFormats {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jsonl" | "json" => Ok(StreamFormats::Jsonl),
+            "csv" => Ok(StreamFormats::Csv),
+            "binary" | "bin" => Ok(StreamFormats::Binary),
+            _ => Err(format!("Unknown stream format: {}", s)),
+        }
+    }
+}
+
+/*
+ * Writes frames to stdout until the count is reached or the reader goes away.
+ * The sensor is only read once the previous record was written, so a slow
+ * reader gets the newest frame instead of a growing backlog.
+ * max_rate limits the records per second below the sensor framerate.
+ */
+pub fn run(opt: &Opt, format: StreamFormats, grid: bool, count: Option<usize>, max_rate: Option<f32>) -> Result<(), String> {
+    let min_period = match max_rate {
+        Some(rate) if rate > 0.0 => Some(Duration::from_secs_f32(1.0 / rate)),
+        Some(_) => return Err("Rate must be above zero".to_string()),
+        None => None,
+    };
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    if format == StreamFormats::Csv {
+        let res = write_line(&mut out, &csv_header(grid));
+        if let Some(res) = stop_on_closed(res) { return res; }
+    }
+
+    let mut frames = 0;
+    let mut errors = 0;
+
+    while count.map(|c| frames < c).unwrap_or(true) {
+        let started = Instant::now();
+
+        let (temperatures, ambient_temp) = match read_frame(opt) {
+            Ok(frame) => {
+                errors = 0;
+                frame
+            },
+
+            Err(err) => {
+                errors += 1;
+                eprintln!("Sensor read failed ({}/{}): {}", errors, MAX_CONSECUTIVE_ERRORS, err);
+
+                if errors >= MAX_CONSECUTIVE_ERRORS { return Err(err); }
+
+                thread::sleep(RETRY_DELAY);
+                continue;
+            },
+        };
+
+        let res = match format {
+            StreamFormats::Jsonl => write_jsonl(&mut out, opt, &temperatures, ambient_temp, grid),
+            StreamFormats::Csv => write_csv(&mut out, &temperatures, ambient_temp, grid),
+            StreamFormats::Binary => write_binary(&mut out, &temperatures, ambient_temp, grid),
+        };
+
+        if let Some(res) = stop_on_closed(res.and_then(|_| out.flush())) { return res; }
+
+        frames += 1;
+
+        if let Some(period) = min_period {
+            thread::sleep(period.saturating_sub(started.elapsed()));
+        }
+    }
+
+    return Ok(());
+}
+
+/*
+ * None to go on. A closed pipe (e.g. "| head") ends the stream without error.
+ */
+fn stop_on_closed(res: io::Result<()>) -> Option<Result<(), String>> {
+    return match res {
+        Ok(()) => None,
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Some(Ok(())),
+        Err(err) => Some(Err(format!("Failed to write to stdout: {}", err))),
+    };
+}
+
+fn read_frame(opt: &Opt) -> Result<(mlx::TemperatureRead, f32), String> {
+    let mut temperatures = mlx::read_temperatures()?;
+    temperatures.convert_unit(mlx::TemperatureUnits::Celsius, opt.unit);
+
+    let ambient_temp = opt.unit.from_celsius(mlx::read_ambient_temperature()?);
+
+    return Ok((temperatures, ambient_temp));
+}
+
+fn get_mean(temperatures: &mlx::TemperatureRead) -> f32 {
+    return temperatures.temperature_grid.iter().sum::<f32>() / mlx::PIXEL_COUNT as f32;
+}
+
+fn write_line(out: &mut impl Write, line: &str) -> io::Result<()> {
+    return writeln!(out, "{}", line);
+}
+
+fn write_jsonl(out: &mut impl Write, opt: &Opt, temperatures: &mlx::TemperatureRead, ambient_temp: f32, grid: bool) -> io::Result<()> {
+    let record = Record {
+        timestamp: chrono::offset::Local::now().to_rfc3339(),
+        unit: opt.unit.symbol(),
+        ambient_temp,
+        min_temp: temperatures.min_temp,
+        max_temp: temperatures.max_temp,
+        mean_temp: get_mean(temperatures),
+        width: if grid { Some(mlx::PIXELS_WIDTH) } else { None },
+        height: if grid { Some(mlx::PIXELS_HEIGHT) } else { None },
+        temperature_grid: if grid { Some(&temperatures.temperature_grid) } else { None },
+    };
+
+    let line = serde_json::to_string(&record).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    return write_line(out, &line);
+}
+
+fn csv_header(grid: bool) -> String {
+    let mut columns = vec![
+        "timestamp".to_string(),
+        "ambient_temp".to_string(),
+        "min_temp".to_string(),
+        "max_temp".to_string(),
+        "mean_temp".to_string(),
+    ];
+
+    if grid {
+        for y in 0..mlx::PIXELS_HEIGHT {
+            for x in 0..mlx::PIXELS_WIDTH {
+                columns.push(format!("t_{}_{}", y, x));
+            }
+        }
+    }
+
+    return columns.join(",");
+}
+
+fn write_csv(out: &mut impl Write, temperatures: &mlx::TemperatureRead, ambient_temp: f32, grid: bool) -> io::Result<()> {
+    let mut values = vec![
+        chrono::offset::Local::now().to_rfc3339(),
+        format!("{:.2}", ambient_temp),
+        format!("{:.2}", temperatures.min_temp),
+        format!("{:.2}", temperatures.max_temp),
+        format!("{:.2}", get_mean(temperatures)),
+    ];
+
+    if grid {
+        values.extend(temperatures.temperature_grid.iter().map(|t| format!("{:.2}", t)));
+    }
+
+    return write_line(out, &values.join(","));
+}
+
+fn write_binary(out: &mut impl Write, temperatures: &mlx::TemperatureRead, ambient_temp: f32, grid: bool) -> io::Result<()> {
+    let timestamp = chrono::offset::Utc::now().timestamp_micros() as f64 / 1_000_000.0;
+
+    out.write_all(&timestamp.to_le_bytes())?;
+
+    for value in [ambient_temp, temperatures.min_temp, temperatures.max_temp, get_mean(temperatures)] {
+        out.write_all(&value.to_le_bytes())?;
+    }
+
+    if grid {
+        for t in temperatures.temperature_grid.iter() {
+            out.write_all(&t.to_le_bytes())?;
+        }
+    }
+
+    return Ok(());
+}
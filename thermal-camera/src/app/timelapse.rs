@@ -0,0 +1,162 @@
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeZone};
+
+use super::{bsp, mlx, Opt};
+use bsp::bsp_export;
+
+// A failed sensor read is retried this often before the capture is skipped
+const CAPTURE_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/*
+ * Time between two captures, e.g. "90", "90s", "5m" or "1h"
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Interval(pub Duration);
+
+/*
+ * Last moment a capture is taken, e.g. "06:30" for the next
+ * time the clock shows it, or "2024-01-31 06:30" for a fixed date
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct EndTime(pub DateTime<Local>);
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (value, factor) = match s.chars().last() {
+            Some('s') => (&s[..s.len() - 1], 1),
+            Some('m') => (&s[..s.len() - 1], 60),
+            Some('h') => (&s[..s.len() - 1], 3600),
+            _ => (s, 1),
+        };
+
+        let seconds: u64 = value.trim().parse().map_err(|_| format!("Invalid interval: {}", s))?;
+
+        if seconds == 0 {
+            return Err("Interval must be longer than zero".to_string());
+        }
+
+        return Ok(Interval(Duration::from_secs(seconds * factor)));
+    }
+}
+
+impl FromStr for EndTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let now = Local::now();
+
+        let naive = if let Ok(time) = NaiveTime::parse_from_str(s, "%H:%M") {
+            let today = now.date_naive().and_time(time);
+
+            if today > now.naive_local() { today } else { today + chrono::Duration::days(1) }
+        }
+        else {
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+                .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+                .map_err(|_| format!("Invalid end time: {}", s))?
+        };
+
+        return Local.from_local_datetime(&naive)
+            .earliest()
+            .map(EndTime)
+            .ok_or(format!("End time doesn't exist in the local timezone: {}", s));
+    }
+}
+
+/*
+ * Captures every interval until the count or end time is reached, runs forever without both.
+ * Captures are numbered by the {seq} token, which is appended if the template lacks it.
+ * Failed captures are logged and skipped, so a flaky sensor or
+ * a removed drive don't end an overnight run.
+ * Returns how many captures were saved.
+ */
+pub fn run(opt: &Opt, interval: Interval, count: Option<usize>, until: Option<EndTime>) -> Result<usize, String> {
+    let mut opt = opt.clone();
+
+    if !opt.filename_template.contains("{seq}") {
+        opt.filename_template += "_{seq}";
+    }
+
+    let interval = interval.0;
+    let start = Instant::now();
+
+    let mut saved = 0;
+    let mut slot: u32 = 0;
+
+    println!(
+        "Timelapse started: every {}s{}{}",
+        interval.as_secs(),
+        count.map(|c| format!(", {} captures", c)).unwrap_or_default(),
+        until.map(|u| format!(", until {}", u.0.format("%Y-%m-%d %H:%M"))).unwrap_or_default()
+    );
+
+    loop {
+        if count.map(|c| saved >= c).unwrap_or(false) { break; }
+        if until.map(|u| Local::now() > u.0).unwrap_or(false) { break; }
+
+        match capture(&opt) {
+            Ok(path) => {
+                saved += 1;
+
+                println!(
+                    "[{}{}] Saved {}",
+                    saved,
+                    count.map(|c| format!("/{}", c)).unwrap_or_default(),
+                    path
+                );
+            },
+
+            Err(err) => println!("Capture skipped: {}", err),
+        }
+
+        if count.map(|c| saved >= c).unwrap_or(false) { break; }
+
+        // Slots are relative to the start, so the schedule doesn't drift
+        slot += 1;
+        let now = Instant::now();
+        let elapsed_slots = (now - start).as_secs_f64() / interval.as_secs_f64();
+
+        if (slot as f64) < elapsed_slots {
+            println!("Capture took longer than the interval, skipping ahead");
+            slot = elapsed_slots.ceil() as u32;
+        }
+
+        thread::sleep((start + interval * slot).saturating_duration_since(now));
+    }
+
+    println!("Timelapse finished, {} captures saved", saved);
+
+    return Ok(saved);
+}
+
+fn capture(opt: &Opt) -> Result<String, String> {
+    let image = take_image(opt)?;
+
+    return bsp_export::save_capture(opt, &image).map_err(|err| err.to_string());
+}
+
+fn take_image(opt: &Opt) -> Result<mlx::ImageRead, String> {
+    let mut attempt = 1;
+
+    loop {
+        match mlx::take_image(&opt.color_settings(), opt.unit) {
+            Ok(image) => return Ok(image),
+
+            Err(err) if attempt < CAPTURE_ATTEMPTS => {
+                println!("Sensor read failed ({}/{}): {}", attempt, CAPTURE_ATTEMPTS, err);
+                attempt += 1;
+                thread::sleep(RETRY_DELAY);
+            },
+
+            Err(err) => return Err(format!("Sensor read failed: {}", err)),
+        }
+    }
+}
@@ -2,8 +2,9 @@ use eframe::egui;
 use super::mlx;
 use mlx::ImageRead;
 
-use super::bsp;
+use super::{bsp, live, mqtt, server};
 use std::thread;
+use std::path::PathBuf;
 use std::sync::mpsc::{self, Receiver, Sender};
 
 pub use super::Opt;
@@ -11,21 +12,29 @@ pub use super::Opt;
 mod display;
 mod controls;
 mod options;
+mod gallery;
+mod remote;
+mod toasts;
+
+use toasts::Toast;
 
 // How much of the screen is covered by these widgets
 const SCALE_X_SPACE: f32 = 0.1;
 const CONTROLS_X_SPACE: f32 = 0.12;
 
+// Storage doesn't change often, no need to look for it on every frame
+const STORAGE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
 // Fills rest of space
 const IMAGE_X_SPACE: f32 = 1.0 - SCALE_X_SPACE - CONTROLS_X_SPACE;
 
-pub fn open_window() {
+pub fn open_window(options: Opt, config_path: PathBuf) {
     let native_options = eframe::NativeOptions::default();
 
     eframe::run_native(
         "Thermal Camera",
         native_options,
-        Box::new(|cc| Box::new(ThermalApp::new(cc))),
+        Box::new(|cc| Box::new(ThermalApp::new(cc, options, config_path))),
     )
     .unwrap();
 }
@@ -34,6 +43,10 @@ pub struct ThermalApp {
     window_size: egui::Vec2,
 
     options: Opt,
+    config_path: PathBuf,
+
+    // Options as last written, only what changed since is written again
+    saved_options: Opt,
 
     last_read: Result<mlx::ImageRead, String>,
 
@@ -43,31 +56,82 @@ pub struct ThermalApp {
     raw_scale: Option<[u8; mlx::GRADIENT_COUNT * 3]>,
     scale: Option<egui::TextureHandle>,
     scale_bound: (f32, f32),
+    scale_ticks: [f32; mlx::SCALE_TICK_COUNT],
+
+    recording: Option<bsp::bsp_record::Recording>,
+
+    // Last touched position on the image, relative to its size
+    spot: Option<egui::Pos2>,
 
     show_options: bool,
+    options_page: options::Pages,
+    profile_edit: options::ProfileEdit,
+
+    gallery: Option<gallery::Gallery>,
 
     image_rx: Option<mpsc::Receiver<Result<ImageRead, String>>>,
     rx_active: bool,
     args_tx: Option<mpsc::Sender<Opt>>,
 
-    usb_detected: bool,
+    toasts: toasts::Toasts,
+
+    storage: Option<bsp::bsp_storage::Storage>,
+    storage_rx: Option<mpsc::Receiver<Option<bsp::bsp_storage::Storage>>>,
+
+    // Commands of the HTTP API and MQTT, only set while one of them runs
+    remote_rx: Option<mpsc::Receiver<server::server_control::RemoteRequest>>,
 }
 
 impl ThermalApp {
-    fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let refresh_rate = mlx::read_framerate().unwrap_or(mlx::Framerates::Half);
-
-        let mut saved_options = bsp::read_options().unwrap_or_default();
-        saved_options.framerate = refresh_rate;
+    fn new(_cc: &eframe::CreationContext<'_>, options: Opt, config_path: PathBuf) -> Self {
+        // The sensor forgets its settings on power off
+        mlx::set_framerate(options.framerate);
+        mlx::set_resolution(options.resolution);
+        mlx::set_emissivity(options.emissivity);
 
         let mut s = Self {
-            options: saved_options,
+            saved_options: options.clone(),
+            options,
+            config_path,
             rx_active: true,
             picture_options: egui::TextureOptions::NEAREST,
             scale_bound: (20.0, 40.0),
             ..Default::default()
         };
 
+        let (storage_tx, storage_rx) = mpsc::channel();
+        let save_dir = s.options.save_dir.clone();
+        let ctx_clone = _cc.egui_ctx.clone();
+
+        let toast_tx = s.toasts.sender();
+
+        thread::spawn(move || ThermalApp::watch_storage(save_dir, ctx_clone, storage_tx, toast_tx));
+        s.storage_rx = Some(storage_rx);
+
+        if s.options.http_enabled {
+            if let Err(err) = server::start(&s.options) {
+                s.toasts.push(Toast::Error(err));
+            }
+        }
+
+        if s.options.tcp_enabled {
+            if let Err(err) = server::server_tcp::start(&s.options) {
+                s.toasts.push(Toast::Error(err));
+            }
+        }
+
+        if s.options.mqtt_enabled {
+            if let Err(err) = mqtt::start(&s.options, mlx::TemperatureUnits::Celsius) {
+                s.toasts.push(Toast::Error(err));
+            }
+        }
+
+        // Both take commands, which run in update
+        if s.options.http_enabled || s.options.mqtt_enabled {
+            let ctx_clone = _cc.egui_ctx.clone();
+            s.remote_rx = Some(server::server_control::take_control(move || ctx_clone.request_repaint()));
+        }
+
         display::image::init_image_texture(&mut s, &_cc.egui_ctx);
         display::scale::init_scale(&mut s, &_cc.egui_ctx);
         display::scale::update_scale(&mut s);
@@ -108,28 +172,67 @@ impl ThermalApp {
                 continue;
             }
 
-            let color_grid = mlx::mlx_image::color_image(&args.as_ref().unwrap().color_type, &temp_grid.unwrap());
+            let color_grid = mlx::mlx_image::color_image(&args.as_ref().unwrap().color_settings(), &temp_grid.unwrap());
+            live::publish(args.as_ref().unwrap(), &color_grid, mlx::TemperatureUnits::Celsius);
             tx.send(Ok(color_grid)).unwrap();
             ctx.request_repaint();
         }
     }
 
+    /*
+     * Reports the storage whenever it appears, disappears or its free space changes.
+     * Captures saved internally are moved over as soon as other storage shows up.
+     */
+    fn watch_storage(save_dir: Option<String>, ctx: egui::Context, tx: Sender<Option<bsp::bsp_storage::Storage>>, toast_tx: Sender<Toast>) {
+        let mut last_storage = None;
+
+        loop {
+            let mut storage = bsp::bsp_storage::find_save_target(save_dir.as_deref());
+
+            if let Some(target) = storage.as_ref().filter(|s| !s.internal && s.has_space()) {
+                match bsp::bsp_storage::sync_internal(target) {
+                    Ok(0) => (),
+                    Ok(count) => {
+                        toast_tx.send(Toast::Success(format!("Moved {} saved files to USB", count))).unwrap_or_default();
+                        storage = bsp::bsp_storage::find_save_target(save_dir.as_deref());
+                    },
+                    Err(err) => {
+                        toast_tx.send(Toast::Error(format!("Failed to move saved files to USB: {}", err))).unwrap_or_default();
+                    },
+                }
+            }
+
+            if storage != last_storage {
+                if tx.send(storage.clone()).is_err() { return; }
+
+                last_storage = storage;
+                ctx.request_repaint();
+            }
+
+            thread::sleep(STORAGE_CHECK_INTERVAL);
+        }
+    }
+
     fn recolor_image(&mut self, ctx: &egui::Context) {
         if self.last_read.is_err() { return; }
 
         let last_read = self.last_read.as_ref().unwrap();
 
-        let color_grid = mlx::mlx_image::color_image(&self.options.color_type, &last_read.temperature_read);
+        let color_grid = mlx::mlx_image::color_image(&self.options.color_settings(), &last_read.temperature_read);
         
         let img = egui::ColorImage::from_rgb(
-            [mlx::PIXELS_WIDTH, mlx::PIXELS_HEIGHT],
-            &color_grid.pixels
+            [color_grid.scaled.width, color_grid.scaled.height],
+            &color_grid.scaled.pixels
         );
 
         self.picture.as_mut().unwrap().set(img, self.picture_options);
 
+        self.scale_bound = color_grid.color_range;
+        self.scale_ticks = color_grid.scale_ticks;
         self.last_read = Ok(color_grid);
 
+        display::scale::update_scale(self);
+
         ctx.request_repaint();
     }
 
@@ -138,41 +241,146 @@ impl ThermalApp {
         let tx: &Sender<Opt> = self.args_tx.as_ref().unwrap();
         tx.send(self.options.clone()).unwrap();
 
-        bsp::write_options(&self.options).unwrap_or_else(|_| {
-            println!("Failed to write options");
-        });
+        match bsp::bsp_config::update_config(&self.config_path, &self.saved_options, &self.options) {
+            Ok(()) => self.saved_options = self.options.clone(),
+            Err(err) => println!("Failed to write {}: {}", self.config_path.display(), err),
+        }
 
         display::scale::update_scale(self);
     }
 
-    fn save_image(&mut self) {
-        if self.picture.is_none() { return }
-        if !bsp::check_usb() { return }
+    /*
+     * Shows the outcome as toast, the error is returned for the HTTP API
+     */
+    fn save_image(&mut self) -> Result<(), String> {
+        if self.picture.is_none() { return Err("No image yet".to_string()); }
+        let storage = match self.get_save_storage() {
+            Ok(storage) => storage,
+            Err(err) => {
+                self.toasts.push(Toast::Error(format!("Failed to save image: {}", err)));
+                return Err(err);
+            },
+        };
 
-        let raw_img = self.last_read.as_ref().unwrap_or(&ImageRead::default()).pixels;
+        let default_read = ImageRead::default();
+        let last_read = self.last_read.as_ref().unwrap_or(&default_read);
 
-        let path = bsp::get_usb_path();
+        let format = self.options.save_format;
+        let max_temp = Some(last_read.temperature_read.max_temp);
+        let path = bsp::get_save_path(storage, &self.options, max_temp, format.extensions());
+        let mut metadata = bsp::bsp_export::FrameMetadata::new(&self.options, last_read, mlx::TemperatureUnits::Celsius);
 
-        bsp::write_png(
-            &path,
-            &raw_img,
-            mlx::PIXELS_WIDTH as u32,
-            mlx::PIXELS_HEIGHT as u32,
-        );
+        if let Some(spot) = self.spot {
+            metadata.markers.push(bsp::bsp_export::Marker {
+                name: live::SPOT_MARKER.to_string(),
+                x: spot.x,
+                y: spot.y,
+                temperature: last_read.scaled.get_temperature(spot.x, spot.y),
+            });
+        }
+
+        let res = bsp::bsp_export::export(&path, format, last_read, &metadata)
+            .map_err(|err| format!("Failed to save image: {}", err));
+
+        let toast = match res.as_ref() {
+            Ok(()) if storage.internal => Toast::Success("Image saved internally, moved to USB later".to_string()),
+            Ok(()) => Toast::Success("Image saved".to_string()),
+            Err(err) => Toast::Error(err.clone()),
+        };
+
+        self.toasts.push(toast);
+
+        return res;
+    }
+
+    fn start_recording(&mut self) -> Result<(), String> {
+        let storage = match self.get_save_storage() {
+            Ok(storage) => storage,
+            Err(err) => {
+                self.toasts.push(Toast::Error(format!("Failed to start recording: {}", err)));
+                return Err(err);
+            },
+        };
+
+        self.recording = Some(bsp::bsp_record::Recording::new(
+            self.options.record_format,
+            self.options.framerate,
+            self.options.record_duration,
+            &storage.path
+        ));
+
+        return Ok(());
+    }
+
+    fn stop_recording(&mut self) {
+        let recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => return,
+        };
+
+        let path = match self.storage.as_ref() {
+            Some(storage) => bsp::get_save_path(storage, &self.options, Some(recording.max_temp), &[recording.format.extension()]),
+            None => {
+                self.toasts.push(Toast::Error("Failed to save recording: No storage found".to_string()));
+                return;
+            },
+        };
+
+        let toast_tx = self.toasts.sender();
+
+        // The encoder may still be busy with the last frames, don't block the GUI
+        thread::spawn(move || {
+            let toast = match recording.finish(&path) {
+                Ok(()) => Toast::Success("Recording saved".to_string()),
+                Err(err) => Toast::Error(format!("Failed to save recording: {}", err)),
+            };
+
+            toast_tx.send(toast).unwrap_or_default();
+        });
+    }
+
+    fn update_storage(&mut self) {
+        let rx = match self.storage_rx.as_ref() {
+            Some(rx) => rx,
+            None => return,
+        };
+
+        while let Ok(storage) = rx.try_recv() {
+            self.storage = storage;
+        }
     }
 
-    fn check_usb(&mut self) {
-        self.usb_detected = bsp::check_usb();
+    /*
+     * The storage to save to, if there's space on it
+     */
+    fn get_save_storage(&self) -> Result<&bsp::bsp_storage::Storage, String> {
+        let storage = self.storage.as_ref().ok_or("No storage found".to_string())?;
+        storage.check_space()?;
+
+        return Ok(storage);
+    }
+
+    fn can_save(&self) -> bool {
+        return self.get_save_storage().is_ok();
     }
 }
 
 impl eframe::App for ThermalApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.check_usb();
+        self.update_storage();
+        remote::handle_commands(self, ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             self.window_size = ui.available_size();
 
+            // Gallery covers the whole window, keep receiving frames in the background
+            if self.gallery.is_some() {
+                display::image::update_image(self, ctx);
+                gallery::show(self, ui, ctx);
+                toasts::show(self, ui);
+                return;
+            }
+
             ui.horizontal_centered(|ui| {
                 if !self.options.left_handed {
                     display::show(self, ui, ctx);
@@ -186,6 +394,7 @@ impl eframe::App for ThermalApp {
             });
 
             options::show(self, ui);
+            toasts::show(self, ui);
         });
     }
 }
@@ -196,6 +405,8 @@ impl Default for ThermalApp {
             window_size: egui::Vec2::ZERO,
 
             options: Opt::default(),
+            config_path: PathBuf::new(),
+            saved_options: Opt::default(),
 
             last_read: Err("Not initialized".to_string()),
 
@@ -205,14 +416,28 @@ impl Default for ThermalApp {
             raw_scale: None,
             scale: None,
             scale_bound: (0.0, 0.0),
+            scale_ticks: [0.0; mlx::SCALE_TICK_COUNT],
+
+            recording: None,
+
+            spot: None,
 
             show_options: false,
+            options_page: options::Pages::General,
+            profile_edit: options::ProfileEdit::default(),
+
+            gallery: None,
 
             image_rx: None,
             rx_active: false,
             args_tx: None,
 
-            usb_detected: false,
+            toasts: toasts::Toasts::new(),
+
+            storage: None,
+            storage_rx: None,
+
+            remote_rx: None,
         }
     }
 }
@@ -1,20 +1,24 @@
 use super::{egui, ThermalApp, CONTROLS_X_SPACE};
 
+const STORAGE_LABEL_HEIGHT: f32 = 20.0;
+
 pub fn show(app: &mut ThermalApp, ui: &mut egui::Ui) {
-    const BUTTONS_COUNT: u32 = 3;
+    const BUTTONS_COUNT: u32 = 5;
     let spacing = ui.spacing().item_spacing;
     let width = CONTROLS_X_SPACE * app.window_size.x;
-    let height_buttons = ui.available_height() / BUTTONS_COUNT as f32 - spacing.y;
+    let height_buttons = (ui.available_height() - STORAGE_LABEL_HEIGHT - spacing.y) / BUTTONS_COUNT as f32 - spacing.y;
     let size_buttons = egui::Vec2::new(width, height_buttons);
 
     ui.vertical(|ui| {
+        draw_storage_label(app, ui, egui::vec2(width, STORAGE_LABEL_HEIGHT));
+
         let button_freeze = ui.add_sized(
             size_buttons,
             egui::Button::new("Freeze image")
         );
 
         let button_save = ui.add_enabled_ui(
-            app.usb_detected,
+            app.can_save(),
             |ui| {
                 ui.add_sized(
                     size_buttons,
@@ -23,6 +27,26 @@ pub fn show(app: &mut ThermalApp, ui: &mut egui::Ui) {
             }
         ).inner;
 
+        let record_text = match app.recording.as_ref() {
+            Some(recording) => format!("Stop ({}s)", recording.duration().as_secs()),
+            None => "Record".to_string(),
+        };
+
+        let button_record = ui.add_enabled_ui(
+            app.can_save() || app.recording.is_some(),
+            |ui| {
+                ui.add_sized(
+                    size_buttons,
+                    egui::Button::new(record_text)
+                )
+            }
+        ).inner;
+
+        let button_gallery = ui.add_sized(
+            size_buttons,
+            egui::Button::new("Gallery")
+        );
+
         let button_options = ui.add_sized(
             size_buttons,
             egui::Button::new("Options")
@@ -34,19 +58,77 @@ pub fn show(app: &mut ThermalApp, ui: &mut egui::Ui) {
         if button_save.clicked() {
             on_button_save(app);
         }
+        if button_record.clicked() {
+            on_button_record(app);
+        }
+        if button_gallery.clicked() {
+            on_button_gallery(app);
+        }
         if button_options.clicked() {
             on_button_options(app);
         }
     });
 }
 
+fn draw_storage_label(app: &ThermalApp, ui: &mut egui::Ui, size: egui::Vec2) {
+    let (text, color) = match app.storage.as_ref() {
+        None => ("No storage".to_string(), egui::Color32::RED),
+
+        Some(storage) if !storage.has_space() => (
+            if storage.internal { "Internal full" } else { "USB full" }.to_string(),
+            egui::Color32::RED
+        ),
+
+        Some(storage) => (
+            format!(
+                "{} {} free",
+                if storage.internal { "Internal" } else { "USB" },
+                format_bytes(storage.free_bytes)
+            ),
+            if storage.internal { egui::Color32::YELLOW } else { egui::Color32::GREEN }
+        ),
+    };
+
+    ui.add_sized(size, egui::Label::new(egui::RichText::new(text).color(color)));
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut value = bytes as f32;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    return format!("{:.1} {}", value, UNITS[unit]);
+}
+
 fn on_button_freeze(app: &mut ThermalApp) {
     app.rx_active = !app.rx_active;
 }
 
 fn on_button_save(app: &mut ThermalApp) {
-    app.save_image();
-    println!("Image saved");
+    // The toast already tells about failure
+    app.save_image().unwrap_or_default();
+}
+
+fn on_button_record(app: &mut ThermalApp) {
+    if app.recording.is_some() {
+        app.stop_recording();
+    }
+    else {
+        // The toast already tells about failure
+        app.start_recording().unwrap_or_default();
+    }
+}
+
+fn on_button_gallery(app: &mut ThermalApp) {
+    app.show_options = false;
+    let dir = app.storage.as_ref().map(|s| s.path.as_str());
+    app.gallery = Some(super::gallery::Gallery::new(dir));
 }
 
 fn on_button_options(app: &mut ThermalApp) {
@@ -1,4 +1,4 @@
-use super::{egui, ThermalApp, mlx};
+use super::{egui, ThermalApp, mlx, live};
 use super::{SCALE_X_SPACE, IMAGE_X_SPACE};
 
 pub mod image;
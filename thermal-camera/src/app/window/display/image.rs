@@ -1,4 +1,4 @@
-use super::{egui, ThermalApp, mlx};
+use super::{egui, ThermalApp, mlx, live};
 use super::{IMAGE_X_SPACE, SCALE_X_SPACE};
 
 pub fn show_image(app: &mut ThermalApp, ui: &mut egui::Ui) {
@@ -60,13 +60,28 @@ pub fn update_image(app: &mut ThermalApp, ctx: &egui::Context) {
         let img_read = img_response.unwrap();
         
         let img = egui::ColorImage::from_rgb(
-            [mlx::PIXELS_WIDTH, mlx::PIXELS_HEIGHT],
-            &img_read.pixels
+            [img_read.scaled.width, img_read.scaled.height],
+            &img_read.scaled.pixels
         );
 
         app.picture.as_mut().unwrap().set(img, app.picture_options);
         
-        app.scale_bound = (img_read.temperature_read.min_temp, img_read.temperature_read.max_temp);
+        app.scale_bound = img_read.color_range;
+        app.scale_ticks = img_read.scale_ticks;
+
+        // Isotherm band on the scale moves with the range
+        if app.options.isotherm_mode != mlx::IsothermModes::Off {
+            super::scale::update_scale(app);
+        }
+
+        let recording_full = app.recording
+            .as_mut()
+            .map(|recording| !recording.add_frame(&img_read.scaled))
+            .unwrap_or(false);
+
+        if recording_full {
+            app.stop_recording();
+        }
 
         app.last_read = Ok(img_read);
     }
@@ -96,27 +111,23 @@ pub fn check_clicked(app: &mut ThermalApp, ui: &mut egui::Ui, response: egui::Re
         return;
     }
 
-    // Calculate position inside image
-    let pos = pos_option.unwrap() + egui::vec2(-20.0, -40.0);
-
+    // Position inside the image, 0 to 1 on both axes
     let rect_image = response.rect;
+    let uv = ((pos_option.unwrap() - rect_image.min) / rect_image.size())
+        .clamp(egui::Vec2::ZERO, egui::Vec2::splat(1.0))
+        .to_pos2();
 
-    let uv = egui::Pos2::new(
-        (pos.x - rect_image.left()) / rect_image.right(),
-        (pos.y - rect_image.top()) / rect_image.bottom()
-    );
-
-    let img_coord: (usize, usize) = (
-        (uv.x * mlx::PIXELS_WIDTH as f32).floor().min(mlx::PIXELS_WIDTH as f32).max(0.0) as usize,
-        (uv.y * mlx::PIXELS_HEIGHT as f32).floor().min(mlx::PIXELS_HEIGHT as f32).max(0.0) as usize,
-    );
+    // The label goes above and left of the finger, so it isn't covered
+    let pos = pos_option.unwrap() + egui::vec2(-20.0, -40.0);
 
-    let index = img_coord.0 + img_coord.1 * mlx::PIXELS_WIDTH;
+    app.spot = Some(uv);
+    live::set_spot(Some((uv.x, uv.y)));
 
     let mut temperature = 0.0;
 
+    // Read the interpolated temperature, so it matches what's shown
     if app.last_read.is_ok() {
-        temperature = app.last_read.as_ref().unwrap().temperature_read.temperature_grid[index];
+        temperature = app.last_read.as_ref().unwrap().scaled.get_temperature(uv.x, uv.y);
     }
 
     let temp_string = format!("{:.1} °C", temperature);
@@ -17,16 +17,45 @@ pub fn show_scale(app: &mut ThermalApp, ui: &mut egui::Ui) {
             let size = egui::Vec2::new(width, height);
 
             let sized_texture = egui::load::SizedTexture::new(texture, size);
-            ui.image(sized_texture);
+            let response = ui.image(sized_texture);
 
             ui.label(string_min_temp);
+
+            // Equalized colors aren't spread linearly, so show where the temperatures in between are
+            if app.options.gain_mode == mlx::GainModes::Equalized {
+                draw_ticks(app, ui, response.rect);
+            }
         }
     );
 }
 
+fn draw_ticks(app: &ThermalApp, ui: &mut egui::Ui, rect: egui::Rect) {
+    let bg_col = egui::Color32::BLACK;
+    let txt_col = egui::Color32::WHITE;
+
+    let painter = ui.painter();
+
+    for i in 1..mlx::SCALE_TICK_COUNT - 1 {
+        let t = i as f32 / (mlx::SCALE_TICK_COUNT - 1) as f32;
+        let tick_string = format!("{:.1}", app.scale_ticks[i]);
+
+        let txt_galley = painter.layout_no_wrap(tick_string, egui::FontId::default(), txt_col);
+        let pos = egui::pos2(
+            rect.left(),
+            rect.bottom() - t * rect.height() - txt_galley.rect.height() / 2.0
+        );
+
+        let bg_rect = txt_galley.rect
+            .translate(pos.to_vec2())
+            .expand(2.0);
+
+        painter.rect_filled(bg_rect, egui::Rounding::ZERO, bg_col);
+        painter.galley(pos, txt_galley, txt_col);
+    }
+}
+
 pub fn update_scale(app: &mut ThermalApp) {
-    let color_type = app.options.color_type;
-    let gradient = mlx::get_scale(color_type);
+    let gradient = mlx::get_scale(&app.options.color_settings(), &app.scale_ticks);
 
     app.raw_scale.replace(gradient);
 
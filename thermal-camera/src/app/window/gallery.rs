@@ -0,0 +1,332 @@
+use super::{egui, ThermalApp, Toast, mlx, bsp, live};
+
+const THUMBNAIL_SIZE: egui::Vec2 = egui::vec2(128.0, 96.0);
+const BUTTON_HEIGHT: f32 = 60.0;
+
+// Decoding is slow, spread it over several frames
+const THUMBNAILS_PER_FRAME: usize = 2;
+
+pub struct Gallery {
+    entries: Vec<Entry>,
+    selected: Option<Selected>,
+}
+
+struct Entry {
+    path: String,
+    label: String,
+    thumbnail: Option<egui::TextureHandle>,
+    load_failed: bool,
+}
+
+struct Selected {
+    index: usize,
+    picture: Option<egui::TextureHandle>,
+    picture_size: egui::Vec2,
+    radiometric: bool,
+    confirm_delete: bool,
+}
+
+impl Gallery {
+    pub fn new(dir: Option<&str>) -> Self {
+        let entries = bsp::list_captures(dir.unwrap_or_default())
+            .into_iter()
+            .map(|path| Entry {
+                label: get_label(&path),
+                path,
+                thumbnail: None,
+                load_failed: false,
+            })
+            .collect();
+
+        Self {
+            entries,
+            selected: None,
+        }
+    }
+}
+
+pub fn show(app: &mut ThermalApp, ui: &mut egui::Ui, ctx: &egui::Context) {
+    if app.gallery.is_none() { return }
+
+    let bounds = egui::Rect::from_min_size(egui::Pos2::ZERO, app.window_size);
+    let mut gallery_ui = ui.child_ui(bounds, egui::Layout::top_down(egui::Align::Min));
+
+    let bg_painter = gallery_ui.painter();
+    bg_painter.rect_filled(gallery_ui.max_rect().expand(10.0), 0.0, egui::Color32::BLACK);
+
+    let is_selected = app.gallery.as_ref().unwrap().selected.is_some();
+
+    if is_selected {
+        draw_picture(app, &mut gallery_ui, ctx);
+    }
+    else {
+        load_thumbnails(app.gallery.as_mut().unwrap(), &app.options.color_settings(), ctx);
+        draw_overview(app, &mut gallery_ui, ctx);
+    }
+}
+
+fn draw_overview(app: &mut ThermalApp, ui: &mut egui::Ui, ctx: &egui::Context) {
+    let button_size = egui::vec2(0.0, BUTTON_HEIGHT);
+
+    let btn_close = ui.add_sized(
+        egui::vec2(ui.available_width(), BUTTON_HEIGHT),
+        egui::Button::new("Close")
+    );
+
+    if btn_close.clicked() {
+        app.gallery = None;
+        return;
+    }
+
+    let gallery = app.gallery.as_mut().unwrap();
+
+    if gallery.entries.is_empty() {
+        ui.add_sized(button_size, egui::Label::new("No saved pictures found"));
+        return;
+    }
+
+    let mut clicked: Option<usize> = None;
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            for (i, entry) in gallery.entries.iter().enumerate() {
+                ui.vertical(|ui| {
+                    let btn_entry = match entry.thumbnail.as_ref() {
+                        Some(texture) => ui.add(egui::ImageButton::new(
+                            egui::load::SizedTexture::new(texture, fit_size(texture.size_vec2(), THUMBNAIL_SIZE))
+                        )),
+
+                        None => ui.add_sized(THUMBNAIL_SIZE, egui::Button::new(
+                            if entry.load_failed { "Unreadable" } else { "Loading..." }
+                        )),
+                    };
+
+                    ui.label(&entry.label);
+
+                    if btn_entry.clicked() {
+                        clicked = Some(i);
+                    }
+                });
+            }
+        });
+    });
+
+    if let Some(index) = clicked {
+        select_entry(gallery, index, &app.options.color_settings(), ctx);
+    }
+}
+
+fn draw_picture(app: &mut ThermalApp, ui: &mut egui::Ui, ctx: &egui::Context) {
+    let gallery = app.gallery.as_mut().unwrap();
+    let selected = gallery.selected.as_ref().unwrap();
+    let entry = &gallery.entries[selected.index];
+
+    let mut btn_back_clicked = false;
+    let mut btn_open_clicked = false;
+    let mut btn_delete_clicked = false;
+
+    ui.columns(3, |col| {
+        btn_back_clicked = col[0].add_sized(
+            egui::vec2(0.0, BUTTON_HEIGHT),
+            egui::Button::new("Back")
+        ).clicked();
+
+        btn_open_clicked = col[1].add_enabled_ui(
+            selected.radiometric,
+            |ui| {
+                ui.add_sized(
+                    egui::vec2(0.0, BUTTON_HEIGHT),
+                    egui::Button::new("Open")
+                )
+            }
+        ).inner.clicked();
+
+        let delete_text = if selected.confirm_delete { "Confirm delete" } else { "Delete" };
+
+        btn_delete_clicked = col[2].add_sized(
+            egui::vec2(0.0, BUTTON_HEIGHT),
+            egui::Button::new(delete_text)
+        ).clicked();
+    });
+
+    ui.label(&entry.label);
+
+    match selected.picture.as_ref() {
+        Some(texture) => {
+            let size = fit_size(selected.picture_size, ui.available_size());
+
+            ui.vertical_centered(|ui| {
+                ui.image(egui::load::SizedTexture::new(texture, size));
+            });
+        },
+
+        None => {
+            ui.label("Failed to read picture");
+        },
+    }
+
+    if btn_back_clicked {
+        gallery.selected = None;
+    }
+
+    if btn_open_clicked {
+        open_frozen(app, ctx);
+        return;
+    }
+
+    if btn_delete_clicked {
+        on_btn_delete(gallery);
+    }
+}
+
+fn on_btn_delete(gallery: &mut Gallery) {
+    let selected = gallery.selected.as_mut().unwrap();
+
+    if !selected.confirm_delete {
+        selected.confirm_delete = true;
+        return;
+    }
+
+    let index = selected.index;
+
+    match bsp::delete_capture(&gallery.entries[index].path) {
+        Ok(()) => {
+            gallery.entries.remove(index);
+            gallery.selected = None;
+        },
+
+        Err(err) => {
+            println!("Failed to delete picture: {}", err);
+            selected.confirm_delete = false;
+        },
+    }
+}
+
+/*
+ * Shows the saved temperatures like a frozen live image,
+ * so coloring and spot measurement work on them
+ */
+fn open_frozen(app: &mut ThermalApp, ctx: &egui::Context) {
+    let gallery = app.gallery.as_ref().unwrap();
+    let index = gallery.selected.as_ref().unwrap().index;
+
+    let (temperature_read, _metadata) = match bsp::read_capture_temperatures(&gallery.entries[index].path) {
+        Ok(res) => res,
+        Err(err) => {
+            app.toasts.push(Toast::Error(format!("Failed to open picture: {}", err)));
+            return;
+        },
+    };
+
+    app.rx_active = false;
+    app.spot = None;
+    live::set_spot(None);
+    app.last_read = Ok(mlx::ImageRead {
+        temperature_read,
+        ..Default::default()
+    });
+
+    app.recolor_image(ctx);
+    app.gallery = None;
+}
+
+fn select_entry(gallery: &mut Gallery, index: usize, settings: &mlx::ColorSettings, ctx: &egui::Context) {
+    let entry = &gallery.entries[index];
+
+    let picture = bsp::read_capture_image(&entry.path, None, settings)
+        .map_err(|err| println!("Failed to read picture: {}", err))
+        .ok();
+
+    let picture_size = picture
+        .as_ref()
+        .map(|(_, width, height)| egui::vec2(*width as f32, *height as f32))
+        .unwrap_or(egui::Vec2::ZERO);
+
+    let picture = picture.map(|(pixels, width, height)| {
+        let img = egui::ColorImage::from_rgb([width, height], &pixels);
+        ctx.load_texture("Gallery picture", img, egui::TextureOptions::NEAREST)
+    });
+
+    gallery.selected = Some(Selected {
+        index,
+        picture,
+        picture_size,
+        radiometric: bsp::read_capture_temperatures(&entry.path).is_ok(),
+        confirm_delete: false,
+    });
+}
+
+fn load_thumbnails(gallery: &mut Gallery, settings: &mlx::ColorSettings, ctx: &egui::Context) {
+    let max_size = (THUMBNAIL_SIZE.x as u32, THUMBNAIL_SIZE.y as u32);
+
+    let pending = gallery.entries
+        .iter_mut()
+        .filter(|e| e.thumbnail.is_none() && !e.load_failed)
+        .take(THUMBNAILS_PER_FRAME);
+
+    let mut loaded_any = false;
+
+    for entry in pending {
+        loaded_any = true;
+
+        match bsp::read_capture_image(&entry.path, Some(max_size), settings) {
+            Ok((pixels, width, height)) => {
+                let img = egui::ColorImage::from_rgb([width, height], &pixels);
+                entry.thumbnail = Some(ctx.load_texture(&entry.path, img, egui::TextureOptions::LINEAR));
+            },
+
+            Err(err) => {
+                println!("Failed to read thumbnail {}: {}", entry.path, err);
+                entry.load_failed = true;
+            },
+        }
+    }
+
+    if loaded_any {
+        ctx.request_repaint();
+    }
+}
+
+/*
+ * Largest size with the same aspect ratio that fits into the bounds
+ */
+fn fit_size(size: egui::Vec2, bounds: egui::Vec2) -> egui::Vec2 {
+    if size.x <= 0.0 || size.y <= 0.0 { return bounds; }
+
+    let scale = (bounds.x / size.x).min(bounds.y / size.y);
+    return size * scale;
+}
+
+/*
+ * File names look like "2024-01-31_12-00-00.png", other formats than PNG are named
+ */
+fn get_label(file_path: &str) -> String {
+    let file_name = std::path::Path::new(file_path)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let time = file_name
+        .get(0..19)
+        .and_then(|t| chrono::NaiveDateTime::parse_from_str(t, "%Y-%m-%d_%H-%M-%S").ok());
+
+    let mut label = match time {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => return file_name,
+    };
+
+    if file_name.contains(".annotated.") {
+        label += " (annotated)";
+    }
+
+    let extension = std::path::Path::new(file_path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+
+    if extension != "PNG" {
+        label += &format!(" ({})", extension);
+    }
+
+    return label;
+}
@@ -1,8 +1,43 @@
-use super::{egui, ThermalApp, mlx};
+use super::{egui, ThermalApp, mlx, bsp};
+use super::toasts::Toast;
+use bsp::bsp_config;
 
 const WINDOW_RELATIVE_SIZE: f32 = 0.8;
 const LABEL_WIDTH: f32 = 100.0;
 const LABEL_SELECTED_COL: egui::Color32 = egui::Color32::YELLOW;
+const RANGE_STEP: f32 = 1.0;
+const PERCENTILE_STEP: f32 = 1.0;
+const ISOTHERM_STEP: f32 = 1.0;
+const RECORD_DURATION_STEP: u32 = 5;
+const EMISSIVITY_STEP: f32 = 0.01;
+const PROFILE_BUTTON_WIDTH: f32 = 120.0;
+
+// Exported profiles go into this directory on the USB drive
+const PROFILE_EXPORT_DIR: &str = "profiles";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pages {
+    General,
+    Range,
+    Gain,
+    Isotherm,
+    Image,
+    Files,
+    Sensor,
+    Profiles,
+}
+
+/*
+ * Input of the profiles page, kept between frames
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ProfileEdit {
+    name: String,
+    // Delete has to be tapped twice
+    confirm_delete: bool,
+    // Read when first shown, None reads them again after a change
+    profiles: Option<Vec<String>>,
+}
 
 pub fn show(app: &mut ThermalApp, ui: &mut egui::Ui) {
     if ! app.show_options { return }
@@ -20,29 +55,77 @@ fn draw_options(app: &mut ThermalApp, ui: &mut egui::Ui) {
     let bg_painter = ui.painter();
     bg_painter.rect_filled(ui.max_rect().expand(10.0), 5.0, bg_color);
 
-    const ROWS: u32 = 4;
+    const ROWS: u32 = 6;
     let spacing = ui.spacing().item_spacing;
     let elements_height = ui.available_height() / ROWS as f32 - spacing.y;
     let element_standard_size = egui::vec2(0.0, elements_height);
     let label_size = egui::vec2(LABEL_WIDTH, elements_height / 2.0);
     
     ui.vertical_centered_justified(|ui| {
-        handle_close_button(ui, app, element_standard_size);
-        
         ui.horizontal(|ui| {
-            draw_label_color(ui, &app, label_size);
-            handle_options_color(ui, app, element_standard_size);
+            handle_close_button(ui, app, element_standard_size);
+            handle_page_buttons(ui, app, element_standard_size);
         });
 
-        ui.horizontal(|ui| {
-            draw_label_speed(ui, app, label_size);
-            handle_options_speed(ui, app, element_standard_size);
-        });
+        match app.options_page {
+            Pages::General => draw_page_general(ui, app, label_size, element_standard_size),
+            Pages::Range => draw_page_range(ui, app, label_size, element_standard_size),
+            Pages::Gain => draw_page_gain(ui, app, label_size, element_standard_size),
+            Pages::Isotherm => draw_page_isotherm(ui, app, label_size, element_standard_size),
+            Pages::Image => draw_page_image(ui, app, label_size, element_standard_size),
+            Pages::Files => draw_page_files(ui, app, label_size, element_standard_size),
+            Pages::Sensor => draw_page_sensor(ui, label_size),
+            Pages::Profiles => draw_page_profiles(ui, app, label_size, element_standard_size),
+        }
+    });
+}
 
-        ui.horizontal(|ui| {
-            draw_label_handedness(ui, app, label_size);
-            handle_options_handedness(ui, app, element_standard_size);
-        });
+fn draw_page_general(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2, element_size: egui::Vec2) {
+    ui.horizontal(|ui| {
+        draw_label_color(ui, &app, label_size);
+        handle_options_color(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_palette(ui, label_size);
+        handle_options_palette(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_speed(ui, app, label_size);
+        handle_options_speed(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_emissivity(ui, app, label_size);
+        handle_options_emissivity(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_handedness(ui, app, label_size);
+        handle_options_handedness(ui, app, element_size);
+    });
+}
+
+fn draw_page_range(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2, element_size: egui::Vec2) {
+    ui.horizontal(|ui| {
+        draw_label_range_mode(ui, app, label_size);
+        handle_options_range_mode(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_range_min(ui, app, label_size);
+        handle_options_range_min(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_range_max(ui, app, label_size);
+        handle_options_range_max(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_saturation(ui, app, label_size);
+        handle_options_saturation(ui, app, element_size);
     });
 }
 
@@ -74,6 +157,168 @@ fn handle_close_button(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: eg
     }
 }
 
+fn draw_page_gain(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2, element_size: egui::Vec2) {
+    ui.horizontal(|ui| {
+        draw_label_gain_mode(ui, app, label_size);
+        handle_options_gain_mode(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_percentile_low(ui, app, label_size);
+        handle_options_percentile_low(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_percentile_high(ui, app, label_size);
+        handle_options_percentile_high(ui, app, element_size);
+    });
+}
+
+fn draw_page_isotherm(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2, element_size: egui::Vec2) {
+    ui.horizontal(|ui| {
+        draw_label_isotherm_mode(ui, app, label_size);
+        handle_options_isotherm_mode(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_isotherm_min(ui, app, label_size);
+        handle_options_isotherm_min(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_isotherm_max(ui, app, label_size);
+        handle_options_isotherm_max(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_isotherm_color(ui, app, label_size);
+        handle_options_isotherm_color(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_isotherm_background(ui, app, label_size);
+        handle_options_isotherm_background(ui, app, element_size);
+    });
+}
+
+fn draw_page_image(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2, element_size: egui::Vec2) {
+    ui.horizontal(|ui| {
+        draw_label_interpolation(ui, app, label_size);
+        handle_options_interpolation(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_scale_factor(ui, app, label_size);
+        handle_options_scale_factor(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_save_format(ui, app, label_size);
+        handle_options_save_format(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_record_format(ui, app, label_size);
+        handle_options_record_format(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_record_duration(ui, app, label_size);
+        handle_options_record_duration(ui, app, element_size);
+    });
+}
+
+fn draw_page_files(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2, element_size: egui::Vec2) {
+    ui.horizontal(|ui| {
+        draw_label_job(ui, app, label_size);
+        handle_options_job(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_filename_template(ui, app, label_size);
+        handle_options_filename_template(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_session(ui, app, label_size);
+        handle_options_session(ui, app, element_size);
+    });
+}
+
+/*
+ * About the sensor, two values per row
+ */
+fn draw_page_sensor(ui: &mut egui::Ui, label_size: egui::Vec2) {
+    // Serial and bad pixels don't fit the usual label width
+    let label_size = egui::vec2(label_size.x * 2.0, label_size.y);
+
+    let summary = match mlx::get_sensor_info() {
+        Ok(info) => info.summary(),
+        Err(err) => vec![("Sensor not readable", err)],
+    };
+
+    for row in summary.chunks(2) {
+        ui.horizontal(|ui| {
+            for (name, value) in row {
+                draw_label(ui, label_size, name, value.clone());
+            }
+        });
+    }
+}
+
+fn draw_page_profiles(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2, element_size: egui::Vec2) {
+    ui.horizontal(|ui| {
+        draw_label_profile(ui, app, label_size);
+        handle_options_profile(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_profile_name(ui, label_size);
+        handle_options_profile_name(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_profile_edit(ui, app, label_size);
+        handle_options_profile_edit(ui, app, element_size);
+    });
+
+    ui.horizontal(|ui| {
+        draw_label_profile_exchange(ui, app, label_size);
+        handle_options_profile_exchange(ui, app, element_size);
+    });
+}
+
+fn handle_page_buttons(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let pages = [
+        (Pages::General, "General"),
+        (Pages::Range, "Range"),
+        (Pages::Gain, "Gain"),
+        (Pages::Isotherm, "Isotherm"),
+        (Pages::Image, "Image"),
+        (Pages::Files, "Files"),
+        (Pages::Sensor, "Sensor"),
+        (Pages::Profiles, "Profiles"),
+    ];
+
+    ui.columns(pages.len(), |col| {
+        for (i, (page, page_text)) in pages.iter().enumerate() {
+            let btn_page = col[i].add_enabled_ui(
+                app.options_page != *page,
+                |ui| {
+                    ui.add_sized(
+                        element_size,
+                        egui::Button::new(*page_text)
+                    )
+                }
+            ).inner;
+
+            if btn_page.clicked() {
+                app.options_page = *page;
+            }
+        }
+    });
+}
+
 fn draw_label_color(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
     let label_text = "Color mode";
     let label_val = app.options.color_type.to_string();
@@ -108,6 +353,38 @@ fn handle_options_color(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: e
     });
 }
 
+fn draw_label_palette(ui: &mut egui::Ui, label_size: egui::Vec2) {
+    let label_text = "Custom palettes";
+    let label_val = mlx::get_custom_palettes().len().to_string() + " loaded";
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_palette(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let palettes = mlx::get_custom_palettes();
+
+    if palettes.is_empty() {
+        ui.add_sized(
+            element_size,
+            egui::Label::new("No palette files found")
+        );
+        return;
+    }
+
+    ui.columns(palettes.len(), |col| {
+        for (i, palette) in palettes.iter().enumerate() {
+            let btn_palette = col[i].add_sized(
+                element_size,
+                egui::Button::new(palette.to_string())
+            );
+
+            if btn_palette.clicked() {
+                on_btn_coloring(app, col[i].ctx(), *palette);
+            }
+        }
+    });
+}
+
 fn draw_label_speed(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
     let label_text = "Refresh Rate";
     let label_val = app.options.framerate.to_string() + " fps";
@@ -150,6 +427,47 @@ fn handle_options_speed(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: e
     });
 }
 
+fn draw_label_emissivity(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Emissivity";
+    let label_val = format!("{:.2}", app.options.emissivity);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_emissivity(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let emissivity = app.options.emissivity;
+
+    ui.columns(2, |col| {
+        let btn_emissivity_decrease = col[0].add_enabled_ui(
+            emissivity > mlx::MIN_EMISSIVITY,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Decrease")
+                )
+            }
+        ).inner;
+
+        let btn_emissivity_increase = col[1].add_enabled_ui(
+            emissivity < 1.0,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Increase")
+                )
+            }
+        ).inner;
+
+        if btn_emissivity_decrease.clicked() {
+            on_btn_emissivity(app, emissivity - EMISSIVITY_STEP);
+        }
+
+        if btn_emissivity_increase.clicked() {
+            on_btn_emissivity(app, emissivity + EMISSIVITY_STEP);
+        }
+    });
+}
+
 fn draw_label_handedness(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
     let label_text = "Layout";
     let label_val = match app.options.left_handed {
@@ -194,29 +512,1119 @@ fn handle_options_handedness(ui: &mut egui::Ui, app: &mut ThermalApp, element_si
     });
 }
 
-fn on_btn_close(app: &mut ThermalApp) {
-    app.show_options = false;
-    app.update_options();
+fn draw_label_profile(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Profile";
+    let label_val = app.options.profile.clone().unwrap_or("None".to_string());
+
+    draw_label(ui, label_size, label_text, label_val);
 }
 
-fn on_btn_coloring(app: &mut ThermalApp, ctx: &egui::Context, color_type: mlx::ColorTypes) {
-    app.options.color_type = color_type;
+fn handle_options_profile(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let profiles = get_profiles(app).clone();
 
-    app.show_options = false;
-    app.update_options();
-    app.recolor_image(ctx);
+    if profiles.is_empty() {
+        ui.add_sized(
+            element_size,
+            egui::Label::new("No profiles saved")
+        );
+        return;
+    }
+
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        ui.horizontal(|ui| {
+            for profile in profiles.iter() {
+                let is_active = app.options.profile.as_ref() == Some(profile);
+
+                let btn_profile = ui.add_enabled_ui(
+                    !is_active,
+                    |ui| {
+                        ui.add_sized(
+                            egui::vec2(PROFILE_BUTTON_WIDTH, element_size.y),
+                            egui::Button::new(profile)
+                        )
+                    }
+                ).inner;
+
+                if btn_profile.clicked() {
+                    on_btn_profile(app, ui.ctx(), profile);
+                }
+            }
+        });
+    });
 }
 
-fn on_btn_speed(app: &mut ThermalApp, framerate: mlx::Framerates) {
-    app.options.framerate = framerate;
-    mlx::set_framerate(framerate);
-    
-    app.update_options();
+fn get_profiles(app: &mut ThermalApp) -> &Vec<String> {
+    let config_path = &app.config_path;
+
+    return app.profile_edit.profiles.get_or_insert_with(|| bsp_config::list_profiles(config_path));
 }
 
-fn on_btn_hand(app: &mut ThermalApp, left_hand: bool) {
-    app.options.left_handed = left_hand;
+fn draw_label_profile_name(ui: &mut egui::Ui, label_size: egui::Vec2) {
+    let label_text = "Name";
+    let label_val = "for new / rename".to_string();
 
-    app.show_options = false;
-    app.update_options();
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_profile_name(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    ui.add_sized(
+        egui::vec2(ui.available_width(), element_size.y),
+        egui::TextEdit::singleline(&mut app.profile_edit.name)
+            .hint_text("e.g. roof_inspection")
+    );
+}
+
+fn draw_label_profile_edit(ui: &mut egui::Ui, app: &mut ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Manage";
+    let label_val = format!("{} saved", get_profiles(app).len());
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_profile_edit(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let has_name = !app.profile_edit.name.trim().is_empty();
+    let has_profile = app.options.profile.is_some();
+
+    let delete_text = if app.profile_edit.confirm_delete { "Really delete?" } else { "Delete" };
+
+    ui.columns(4, |col| {
+        let btn_save_new = col[0].add_enabled_ui(
+            has_name,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Save as new")
+                )
+            }
+        ).inner;
+
+        let btn_update = col[1].add_enabled_ui(
+            has_profile,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Update")
+                )
+            }
+        ).inner;
+
+        let btn_rename = col[2].add_enabled_ui(
+            has_profile && has_name,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Rename")
+                )
+            }
+        ).inner;
+
+        let btn_delete = col[3].add_enabled_ui(
+            has_profile,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new(delete_text)
+                )
+            }
+        ).inner;
+
+        if btn_save_new.clicked() {
+            let name = app.profile_edit.name.clone();
+            on_btn_profile_save(app, &name);
+        }
+
+        if btn_update.clicked() {
+            let name = app.options.profile.clone().unwrap();
+            on_btn_profile_save(app, &name);
+        }
+
+        if btn_rename.clicked() {
+            on_btn_profile_rename(app);
+        }
+
+        if btn_delete.clicked() {
+            on_btn_profile_delete(app);
+        }
+    });
+}
+
+fn draw_label_profile_exchange(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Exchange";
+    let label_val = match get_profile_export_dir(app) {
+        Some(_) => "USB".to_string(),
+        None => "No USB drive".to_string(),
+    };
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_profile_exchange(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let has_usb = get_profile_export_dir(app).is_some();
+    let has_profile = app.options.profile.is_some();
+
+    ui.columns(2, |col| {
+        let btn_export = col[0].add_enabled_ui(
+            has_usb && has_profile,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Export to USB")
+                )
+            }
+        ).inner;
+
+        let btn_import = col[1].add_enabled_ui(
+            has_usb,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Import from USB")
+                )
+            }
+        ).inner;
+
+        if btn_export.clicked() {
+            on_btn_profile_export(app);
+        }
+
+        if btn_import.clicked() {
+            on_btn_profile_import(app);
+        }
+    });
+}
+
+/*
+ * Profiles are only exchanged with removable storage, not the internal fallback
+ */
+fn get_profile_export_dir(app: &ThermalApp) -> Option<std::path::PathBuf> {
+    let storage = app.storage.as_ref().filter(|s| !s.internal)?;
+
+    return Some(std::path::Path::new(&storage.path).join(PROFILE_EXPORT_DIR));
+}
+
+fn draw_label_range_mode(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Range";
+    let label_val = app.options.range_mode.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_range_mode(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let modes = [
+        (mlx::RangeModes::Auto, "Auto"),
+        (mlx::RangeModes::Locked, "Lock"),
+        (mlx::RangeModes::Manual, "Manual"),
+    ];
+
+    ui.columns(modes.len(), |col| {
+        for (i, (mode, mode_text)) in modes.iter().enumerate() {
+            let btn_mode = col[i].add_sized(
+                element_size,
+                egui::Button::new(*mode_text)
+            );
+
+            if btn_mode.clicked() {
+                on_btn_range_mode(app, col[i].ctx(), *mode);
+            }
+        }
+    });
+}
+
+fn draw_label_range_min(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Minimum";
+    let label_val = format!("{:.1} °C", app.options.range_min);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_range_min(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let can_increase = app.options.range_min + RANGE_STEP < app.options.range_max;
+
+    ui.columns(2, |col| {
+        let btn_min_decrease = col[0].add_sized(
+            element_size,
+            egui::Button::new("Decrease")
+        );
+
+        let btn_min_increase = col[1].add_enabled_ui(
+            can_increase,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Increase")
+                )
+            }
+        ).inner;
+
+        if btn_min_decrease.clicked() {
+            let range = (app.options.range_min - RANGE_STEP, app.options.range_max);
+            on_btn_range(app, col[0].ctx(), range);
+        }
+
+        if btn_min_increase.clicked() {
+            let range = (app.options.range_min + RANGE_STEP, app.options.range_max);
+            on_btn_range(app, col[1].ctx(), range);
+        }
+    });
+}
+
+fn draw_label_range_max(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Maximum";
+    let label_val = format!("{:.1} °C", app.options.range_max);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_range_max(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let can_decrease = app.options.range_max - RANGE_STEP > app.options.range_min;
+
+    ui.columns(2, |col| {
+        let btn_max_decrease = col[0].add_enabled_ui(
+            can_decrease,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Decrease")
+                )
+            }
+        ).inner;
+
+        let btn_max_increase = col[1].add_sized(
+            element_size,
+            egui::Button::new("Increase")
+        );
+
+        if btn_max_decrease.clicked() {
+            let range = (app.options.range_min, app.options.range_max - RANGE_STEP);
+            on_btn_range(app, col[0].ctx(), range);
+        }
+
+        if btn_max_increase.clicked() {
+            let range = (app.options.range_min, app.options.range_max + RANGE_STEP);
+            on_btn_range(app, col[1].ctx(), range);
+        }
+    });
+}
+
+fn draw_label_saturation(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Out of range";
+    let label_val = app.options.saturation.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_saturation(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let saturations = [
+        mlx::SaturationColors::Off,
+        mlx::SaturationColors::BlackWhite,
+        mlx::SaturationColors::WhiteBlack,
+    ];
+
+    ui.columns(saturations.len(), |col| {
+        for (i, saturation) in saturations.iter().enumerate() {
+            let btn_saturation = col[i].add_sized(
+                element_size,
+                egui::Button::new(saturation.to_string())
+            );
+
+            if btn_saturation.clicked() {
+                on_btn_saturation(app, col[i].ctx(), *saturation);
+            }
+        }
+    });
+}
+
+fn draw_label_gain_mode(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Gain";
+    let label_val = app.options.gain_mode.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_gain_mode(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let modes = [
+        (mlx::GainModes::Linear, "Linear"),
+        (mlx::GainModes::Percentile, "Percentile"),
+        (mlx::GainModes::Equalized, "Equalize"),
+    ];
+
+    ui.columns(modes.len(), |col| {
+        for (i, (mode, mode_text)) in modes.iter().enumerate() {
+            let btn_mode = col[i].add_sized(
+                element_size,
+                egui::Button::new(*mode_text)
+            );
+
+            if btn_mode.clicked() {
+                on_btn_gain_mode(app, col[i].ctx(), *mode);
+            }
+        }
+    });
+}
+
+fn draw_label_percentile_low(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Lower clip";
+    let label_val = format!("{:.0} %", app.options.percentile_low);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_percentile_low(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let can_decrease = app.options.percentile_low - PERCENTILE_STEP >= 0.0;
+    let can_increase = app.options.percentile_low + PERCENTILE_STEP < app.options.percentile_high;
+
+    ui.columns(2, |col| {
+        let btn_low_decrease = col[0].add_enabled_ui(
+            can_decrease,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Decrease")
+                )
+            }
+        ).inner;
+
+        let btn_low_increase = col[1].add_enabled_ui(
+            can_increase,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Increase")
+                )
+            }
+        ).inner;
+
+        if btn_low_decrease.clicked() {
+            let percentile = (app.options.percentile_low - PERCENTILE_STEP, app.options.percentile_high);
+            on_btn_percentile(app, col[0].ctx(), percentile);
+        }
+
+        if btn_low_increase.clicked() {
+            let percentile = (app.options.percentile_low + PERCENTILE_STEP, app.options.percentile_high);
+            on_btn_percentile(app, col[1].ctx(), percentile);
+        }
+    });
+}
+
+fn draw_label_percentile_high(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Upper clip";
+    let label_val = format!("{:.0} %", app.options.percentile_high);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_percentile_high(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let can_decrease = app.options.percentile_high - PERCENTILE_STEP > app.options.percentile_low;
+    let can_increase = app.options.percentile_high + PERCENTILE_STEP <= 100.0;
+
+    ui.columns(2, |col| {
+        let btn_high_decrease = col[0].add_enabled_ui(
+            can_decrease,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Decrease")
+                )
+            }
+        ).inner;
+
+        let btn_high_increase = col[1].add_enabled_ui(
+            can_increase,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Increase")
+                )
+            }
+        ).inner;
+
+        if btn_high_decrease.clicked() {
+            let percentile = (app.options.percentile_low, app.options.percentile_high - PERCENTILE_STEP);
+            on_btn_percentile(app, col[0].ctx(), percentile);
+        }
+
+        if btn_high_increase.clicked() {
+            let percentile = (app.options.percentile_low, app.options.percentile_high + PERCENTILE_STEP);
+            on_btn_percentile(app, col[1].ctx(), percentile);
+        }
+    });
+}
+
+fn draw_label_isotherm_mode(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Isotherm";
+    let label_val = app.options.isotherm_mode.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_isotherm_mode(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let modes = [
+        mlx::IsothermModes::Off,
+        mlx::IsothermModes::Band,
+        mlx::IsothermModes::Above,
+        mlx::IsothermModes::Below,
+    ];
+
+    ui.columns(modes.len(), |col| {
+        for (i, mode) in modes.iter().enumerate() {
+            let btn_mode = col[i].add_sized(
+                element_size,
+                egui::Button::new(mode.to_string())
+            );
+
+            if btn_mode.clicked() {
+                app.options.isotherm_mode = *mode;
+                on_isotherm_changed(app, col[i].ctx());
+            }
+        }
+    });
+}
+
+fn draw_label_isotherm_min(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Lower bound";
+    let label_val = format!("{:.1} °C", app.options.isotherm_min);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_isotherm_min(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let can_increase = app.options.isotherm_min + ISOTHERM_STEP <= app.options.isotherm_max;
+
+    ui.columns(2, |col| {
+        let btn_min_decrease = col[0].add_sized(
+            element_size,
+            egui::Button::new("Decrease")
+        );
+
+        let btn_min_increase = col[1].add_enabled_ui(
+            can_increase,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Increase")
+                )
+            }
+        ).inner;
+
+        if btn_min_decrease.clicked() {
+            app.options.isotherm_min -= ISOTHERM_STEP;
+            on_isotherm_changed(app, col[0].ctx());
+        }
+
+        if btn_min_increase.clicked() {
+            app.options.isotherm_min += ISOTHERM_STEP;
+            on_isotherm_changed(app, col[1].ctx());
+        }
+    });
+}
+
+fn draw_label_isotherm_max(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Upper bound";
+    let label_val = format!("{:.1} °C", app.options.isotherm_max);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_isotherm_max(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let can_decrease = app.options.isotherm_max - ISOTHERM_STEP >= app.options.isotherm_min;
+
+    ui.columns(2, |col| {
+        let btn_max_decrease = col[0].add_enabled_ui(
+            can_decrease,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Decrease")
+                )
+            }
+        ).inner;
+
+        let btn_max_increase = col[1].add_sized(
+            element_size,
+            egui::Button::new("Increase")
+        );
+
+        if btn_max_decrease.clicked() {
+            app.options.isotherm_max -= ISOTHERM_STEP;
+            on_isotherm_changed(app, col[0].ctx());
+        }
+
+        if btn_max_increase.clicked() {
+            app.options.isotherm_max += ISOTHERM_STEP;
+            on_isotherm_changed(app, col[1].ctx());
+        }
+    });
+}
+
+fn draw_label_isotherm_color(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Marker color";
+    let label_val = app.options.isotherm_color.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_isotherm_color(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let colors = [
+        mlx::IsothermColors::Green,
+        mlx::IsothermColors::Magenta,
+        mlx::IsothermColors::Cyan,
+    ];
+
+    ui.columns(colors.len(), |col| {
+        for (i, color) in colors.iter().enumerate() {
+            let btn_color = col[i].add_sized(
+                element_size,
+                egui::Button::new(color.to_string())
+            );
+
+            if btn_color.clicked() {
+                app.options.isotherm_color = *color;
+                on_isotherm_changed(app, col[i].ctx());
+            }
+        }
+    });
+}
+
+fn draw_label_isotherm_background(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Background";
+    let label_val = match app.options.isotherm_gray {
+        false => "Palette".into(),
+        true => "Grayscale".into(),
+    };
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_isotherm_background(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let is_gray = app.options.isotherm_gray;
+
+    ui.columns(2, |col| {
+        let btn_palette = col[0].add_enabled_ui(
+            is_gray,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Palette")
+                )
+            }
+        ).inner;
+
+        let btn_gray = col[1].add_enabled_ui(
+            !is_gray,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Grayscale")
+                )
+            }
+        ).inner;
+
+        if btn_palette.clicked() {
+            app.options.isotherm_gray = false;
+            on_isotherm_changed(app, col[0].ctx());
+        }
+
+        if btn_gray.clicked() {
+            app.options.isotherm_gray = true;
+            on_isotherm_changed(app, col[1].ctx());
+        }
+    });
+}
+
+fn draw_label_interpolation(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Interpolation";
+    let label_val = app.options.interpolation.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_interpolation(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let interpolations = [
+        mlx::Interpolations::Nearest,
+        mlx::Interpolations::Bilinear,
+        mlx::Interpolations::Bicubic,
+        mlx::Interpolations::Lanczos,
+    ];
+
+    ui.columns(interpolations.len(), |col| {
+        for (i, interpolation) in interpolations.iter().enumerate() {
+            let btn_interpolation = col[i].add_sized(
+                element_size,
+                egui::Button::new(interpolation.to_string())
+            );
+
+            if btn_interpolation.clicked() {
+                app.options.interpolation = *interpolation;
+
+                app.update_options();
+                app.recolor_image(col[i].ctx());
+            }
+        }
+    });
+}
+
+fn draw_label_scale_factor(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Output scale";
+    let label_val = format!(
+        "{}x ({}x{})",
+        app.options.scale_factor,
+        mlx::PIXELS_WIDTH * app.options.scale_factor,
+        mlx::PIXELS_HEIGHT * app.options.scale_factor
+    );
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_scale_factor(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let is_on_lower_bound = app.options.scale_factor <= 1;
+    let is_on_upper_bound = app.options.scale_factor >= mlx::MAX_SCALE_FACTOR;
+
+    ui.columns(2, |col| {
+        let btn_scale_decrease = col[0].add_enabled_ui(
+            !is_on_lower_bound,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Decrease")
+                )
+            }
+        ).inner;
+
+        let btn_scale_increase = col[1].add_enabled_ui(
+            !is_on_upper_bound,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Increase")
+                )
+            }
+        ).inner;
+
+        if btn_scale_decrease.clicked() {
+            app.options.scale_factor -= 1;
+
+            app.update_options();
+            app.recolor_image(col[0].ctx());
+        }
+
+        if btn_scale_increase.clicked() {
+            app.options.scale_factor += 1;
+
+            app.update_options();
+            app.recolor_image(col[1].ctx());
+        }
+    });
+}
+
+fn draw_label_save_format(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Save format";
+    let label_val = app.options.save_format.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_save_format(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let formats = [
+        bsp::bsp_export::ExportFormats::Png,
+        bsp::bsp_export::ExportFormats::Tiff,
+        bsp::bsp_export::ExportFormats::Csv,
+        bsp::bsp_export::ExportFormats::Npy,
+        bsp::bsp_export::ExportFormats::Annotated,
+    ];
+
+    ui.columns(formats.len(), |col| {
+        for (i, format) in formats.iter().enumerate() {
+            let btn_format = col[i].add_sized(
+                element_size,
+                egui::Button::new(format.to_string())
+            );
+
+            if btn_format.clicked() {
+                app.options.save_format = *format;
+                app.update_options();
+            }
+        }
+    });
+}
+
+fn draw_label_record_format(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Record format";
+    let label_val = app.options.record_format.to_string();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_record_format(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let formats = [
+        bsp::bsp_record::RecordFormats::Gif,
+        bsp::bsp_record::RecordFormats::Apng,
+    ];
+
+    ui.columns(formats.len(), |col| {
+        for (i, format) in formats.iter().enumerate() {
+            let btn_format = col[i].add_sized(
+                element_size,
+                egui::Button::new(format.to_string())
+            );
+
+            if btn_format.clicked() {
+                app.options.record_format = *format;
+                app.update_options();
+            }
+        }
+    });
+}
+
+fn draw_label_record_duration(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Max recording";
+    let label_val = format!("{} s", app.options.record_duration);
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_record_duration(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let is_on_lower_bound = app.options.record_duration <= RECORD_DURATION_STEP;
+    let is_on_upper_bound = app.options.record_duration >= bsp::bsp_record::MAX_DURATION;
+
+    ui.columns(2, |col| {
+        let btn_duration_decrease = col[0].add_enabled_ui(
+            !is_on_lower_bound,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Shorter")
+                )
+            }
+        ).inner;
+
+        let btn_duration_increase = col[1].add_enabled_ui(
+            !is_on_upper_bound,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("Longer")
+                )
+            }
+        ).inner;
+
+        if btn_duration_decrease.clicked() {
+            app.options.record_duration -= RECORD_DURATION_STEP;
+            app.update_options();
+        }
+
+        if btn_duration_increase.clicked() {
+            app.options.record_duration = (app.options.record_duration + RECORD_DURATION_STEP)
+                .min(bsp::bsp_record::MAX_DURATION);
+            app.update_options();
+        }
+    });
+}
+
+fn draw_label_job(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Job / Site";
+    let label_val = if app.options.job.is_empty() { "None".to_string() } else { app.options.job.clone() };
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_job(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let txt_job = ui.add_sized(
+        egui::vec2(ui.available_width(), element_size.y),
+        egui::TextEdit::singleline(&mut app.options.job)
+    );
+
+    if txt_job.lost_focus() {
+        app.update_options();
+    }
+}
+
+fn draw_label_filename_template(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "File name";
+    let label_val = app.options.filename_template.clone();
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_filename_template(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let txt_template = ui.add_sized(
+        egui::vec2(ui.available_width(), element_size.y),
+        egui::TextEdit::singleline(&mut app.options.filename_template)
+            .hint_text("{date} {time} {seq} {job} {session} {max}")
+    );
+
+    if txt_template.lost_focus() {
+        if app.options.filename_template.trim().is_empty() {
+            app.options.filename_template = bsp::bsp_naming::DEFAULT_TEMPLATE.to_string();
+        }
+
+        app.update_options();
+    }
+}
+
+fn draw_label_session(ui: &mut egui::Ui, app: &ThermalApp, label_size: egui::Vec2) {
+    let label_text = "Session";
+    let label_val = app.options.session.clone().unwrap_or("None".to_string());
+
+    draw_label(ui, label_size, label_text, label_val);
+}
+
+fn handle_options_session(ui: &mut egui::Ui, app: &mut ThermalApp, element_size: egui::Vec2) {
+    let has_session = app.options.session.is_some();
+
+    ui.columns(2, |col| {
+        let btn_session_start = col[0].add_sized(
+            element_size,
+            egui::Button::new("New session")
+        );
+
+        let btn_session_end = col[1].add_enabled_ui(
+            has_session,
+            |ui| {
+                ui.add_sized(
+                    element_size,
+                    egui::Button::new("End session")
+                )
+            }
+        ).inner;
+
+        if btn_session_start.clicked() {
+            on_btn_session_start(app);
+        }
+
+        if btn_session_end.clicked() {
+            app.options.session = None;
+            app.update_options();
+        }
+    });
+}
+
+/*
+ * Sessions are named after job and start time
+ */
+fn on_btn_session_start(app: &mut ThermalApp) {
+    let time = chrono::offset::Local::now().format("%Y-%m-%d_%H-%M-%S");
+    let job = bsp::bsp_naming::sanitize(&app.options.job);
+
+    let session = if job.is_empty() {
+        time.to_string()
+    }
+    else {
+        format!("{}_{}", job, time)
+    };
+
+    app.options.session = Some(session);
+    app.update_options();
+}
+
+fn on_btn_close(app: &mut ThermalApp) {
+    app.show_options = false;
+    app.update_options();
+}
+
+fn on_btn_coloring(app: &mut ThermalApp, ctx: &egui::Context, color_type: mlx::ColorTypes) {
+    app.options.color_type = color_type;
+
+    app.show_options = false;
+    app.update_options();
+    app.recolor_image(ctx);
+}
+
+fn on_btn_speed(app: &mut ThermalApp, framerate: mlx::Framerates) {
+    app.options.framerate = framerate;
+    mlx::set_framerate(framerate);
+    
+    app.update_options();
+}
+
+fn on_btn_emissivity(app: &mut ThermalApp, emissivity: f32) {
+    // Rounded, so repeated steps don't accumulate float errors
+    let emissivity = (emissivity / EMISSIVITY_STEP).round() * EMISSIVITY_STEP;
+
+    app.options.emissivity = emissivity.clamp(mlx::MIN_EMISSIVITY, 1.0);
+    mlx::set_emissivity(app.options.emissivity);
+
+    app.update_options();
+}
+
+fn on_btn_hand(app: &mut ThermalApp, left_hand: bool) {
+    app.options.left_handed = left_hand;
+
+    app.show_options = false;
+    app.update_options();
+}
+
+fn on_btn_range_mode(app: &mut ThermalApp, ctx: &egui::Context, range_mode: mlx::RangeModes) {
+    // Locking, or switching away from auto, starts from the range currently shown
+    let take_current_range = range_mode == mlx::RangeModes::Locked
+        || app.options.range_mode == mlx::RangeModes::Auto;
+
+    if take_current_range && app.last_read.is_ok() {
+        app.options.range_min = app.scale_bound.0;
+        app.options.range_max = app.scale_bound.1;
+    }
+
+    app.options.range_mode = range_mode;
+
+    app.update_options();
+    app.recolor_image(ctx);
+}
+
+fn on_btn_range(app: &mut ThermalApp, ctx: &egui::Context, range: (f32, f32)) {
+    app.options.range_mode = mlx::RangeModes::Manual;
+    app.options.range_min = range.0;
+    app.options.range_max = range.1;
+
+    app.update_options();
+    app.recolor_image(ctx);
+}
+
+fn on_btn_saturation(app: &mut ThermalApp, ctx: &egui::Context, saturation: mlx::SaturationColors) {
+    app.options.saturation = saturation;
+
+    app.update_options();
+    app.recolor_image(ctx);
+}
+
+fn on_btn_gain_mode(app: &mut ThermalApp, ctx: &egui::Context, gain_mode: mlx::GainModes) {
+    app.options.gain_mode = gain_mode;
+
+    app.update_options();
+    app.recolor_image(ctx);
+}
+
+fn on_btn_percentile(app: &mut ThermalApp, ctx: &egui::Context, percentile: (f32, f32)) {
+    app.options.gain_mode = mlx::GainModes::Percentile;
+    app.options.percentile_low = percentile.0;
+    app.options.percentile_high = percentile.1;
+
+    app.update_options();
+    app.recolor_image(ctx);
+}
+
+fn on_isotherm_changed(app: &mut ThermalApp, ctx: &egui::Context) {
+    app.update_options();
+    app.recolor_image(ctx);
+}
+
+/*
+ * Settings kept by the sensor itself have to be sent again after switching
+ */
+fn on_btn_profile(app: &mut ThermalApp, ctx: &egui::Context, name: &str) {
+    if let Err(err) = bsp_config::apply_profile(&app.config_path, name, &mut app.options) {
+        app.toasts.push(Toast::Error(err));
+        return;
+    }
+
+    mlx::set_framerate(app.options.framerate);
+    mlx::set_resolution(app.options.resolution);
+    mlx::set_emissivity(app.options.emissivity);
+
+    app.profile_edit.confirm_delete = false;
+
+    app.update_options();
+    app.recolor_image(ctx);
+
+    app.toasts.push(Toast::Success(format!("Profile {} active", name)));
+}
+
+fn on_btn_profile_save(app: &mut ThermalApp, name: &str) {
+    let toast = match bsp_config::save_profile(&app.config_path, name, &app.options) {
+        Ok(name) => {
+            app.options.profile = Some(name.clone());
+            app.profile_edit.name.clear();
+            app.profile_edit.profiles = None;
+            app.update_options();
+
+            Toast::Success(format!("Profile {} saved", name))
+        },
+
+        Err(err) => Toast::Error(format!("Failed to save profile: {}", err)),
+    };
+
+    app.toasts.push(toast);
+}
+
+fn on_btn_profile_rename(app: &mut ThermalApp) {
+    let name = app.options.profile.clone().unwrap();
+
+    let toast = match bsp_config::rename_profile(&app.config_path, &name, &app.profile_edit.name) {
+        Ok(new_name) => {
+            app.options.profile = Some(new_name.clone());
+            app.profile_edit.name.clear();
+            app.profile_edit.profiles = None;
+            app.update_options();
+
+            Toast::Success(format!("Profile {} renamed to {}", name, new_name))
+        },
+
+        Err(err) => Toast::Error(format!("Failed to rename profile: {}", err)),
+    };
+
+    app.toasts.push(toast);
+}
+
+fn on_btn_profile_delete(app: &mut ThermalApp) {
+    if !app.profile_edit.confirm_delete {
+        app.profile_edit.confirm_delete = true;
+        return;
+    }
+
+    app.profile_edit.confirm_delete = false;
+    let name = app.options.profile.clone().unwrap();
+
+    // The settings stay as they are, they just don't belong to a profile anymore
+    let toast = match bsp_config::delete_profile(&app.config_path, &name) {
+        Ok(()) => {
+            app.options.profile = None;
+            app.profile_edit.profiles = None;
+            app.update_options();
+
+            Toast::Success(format!("Profile {} deleted", name))
+        },
+
+        Err(err) => Toast::Error(format!("Failed to delete profile: {}", err)),
+    };
+
+    app.toasts.push(toast);
+}
+
+fn on_btn_profile_export(app: &mut ThermalApp) {
+    let name = app.options.profile.clone().unwrap();
+    let target_dir = get_profile_export_dir(app).unwrap();
+
+    let toast = match bsp_config::export_profile(&app.config_path, &name, &target_dir) {
+        Ok(_) => Toast::Success(format!("Profile {} exported to USB", name)),
+        Err(err) => Toast::Error(format!("Failed to export profile: {}", err)),
+    };
+
+    app.toasts.push(toast);
+}
+
+fn on_btn_profile_import(app: &mut ThermalApp) {
+    let files = bsp_config::list_profile_files(&get_profile_export_dir(app).unwrap());
+
+    if files.is_empty() {
+        app.toasts.push(Toast::Error(format!("No profiles found in {} on USB", PROFILE_EXPORT_DIR)));
+        return;
+    }
+
+    let mut imported = 0;
+
+    for file in files.iter() {
+        match bsp_config::import_profile(&app.config_path, file) {
+            Ok(_) => imported += 1,
+            Err(err) => println!("Failed to import {}: {}", file.display(), err),
+        }
+    }
+
+    app.profile_edit.profiles = None;
+
+    let toast = if imported == files.len() {
+        Toast::Success(format!("Imported {} profiles", imported))
+    }
+    else {
+        Toast::Error(format!("Imported {} of {} profiles", imported, files.len()))
+    };
+
+    app.toasts.push(toast);
 }
@@ -0,0 +1,61 @@
+use super::{egui, ThermalApp};
+use super::server::server_control::{RemoteCommands, RemoteSettings, SettingsChange};
+
+/*
+ * Runs the commands received over HTTP or MQTT like the matching buttons,
+ * so screen, worker thread and config file are updated the same way
+ */
+pub fn handle_commands(app: &mut ThermalApp, ctx: &egui::Context) {
+    let requests: Vec<_> = match app.remote_rx.as_ref() {
+        Some(rx) => rx.try_iter().collect(),
+        None => return,
+    };
+
+    for request in requests {
+        let res = run_command(app, ctx, &request.command).map(|_| get_settings(app));
+        request.reply(res);
+    }
+}
+
+fn run_command(app: &mut ThermalApp, ctx: &egui::Context, command: &RemoteCommands) -> Result<(), String> {
+    match command {
+        RemoteCommands::GetSettings => (),
+
+        RemoteCommands::ChangeSettings(change) => change_settings(app, ctx, change)?,
+
+        RemoteCommands::Save => app.save_image()?,
+
+        RemoteCommands::StartRecording => {
+            if app.recording.is_some() {
+                return Err("Already recording".to_string());
+            }
+
+            app.start_recording()?;
+        },
+
+        RemoteCommands::StopRecording => {
+            if app.recording.is_none() {
+                return Err("Not recording".to_string());
+            }
+
+            app.stop_recording();
+        },
+
+        RemoteCommands::Freeze(frozen) => app.rx_active = !frozen,
+    }
+
+    return Ok(());
+}
+
+fn change_settings(app: &mut ThermalApp, ctx: &egui::Context, change: &SettingsChange) -> Result<(), String> {
+    change.apply(&mut app.options)?;
+
+    app.update_options();
+    app.recolor_image(ctx);
+
+    return Ok(());
+}
+
+fn get_settings(app: &ThermalApp) -> RemoteSettings {
+    return RemoteSettings::new(&app.options, !app.rx_active, app.recording.is_some());
+}
@@ -0,0 +1,93 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use super::{egui, ThermalApp};
+
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+const TOAST_SPACING: f32 = 30.0;
+
+#[derive(Debug, Clone)]
+pub enum Toast {
+    Success(String),
+    Error(String),
+}
+
+/*
+ * Short notifications at the bottom of the screen.
+ * Background threads get a sender, so they can report as well.
+ */
+pub struct Toasts {
+    tx: Sender<Toast>,
+    rx: Receiver<Toast>,
+    active: Vec<(Toast, Instant)>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        Self {
+            tx,
+            rx,
+            active: Vec::new(),
+        }
+    }
+
+    pub fn sender(&self) -> Sender<Toast> {
+        return self.tx.clone();
+    }
+
+    pub fn push(&mut self, toast: Toast) {
+        println!("{}", toast.text());
+        self.active.push((toast, Instant::now()));
+    }
+}
+
+impl Toast {
+    fn text(&self) -> &str {
+        match self {
+            Toast::Success(text) => text,
+            Toast::Error(text) => text,
+        }
+    }
+
+    fn color(&self) -> egui::Color32 {
+        match self {
+            Toast::Success(_) => egui::Color32::WHITE,
+            Toast::Error(_) => egui::Color32::RED,
+        }
+    }
+}
+
+pub fn show(app: &mut ThermalApp, ui: &mut egui::Ui) {
+    while let Ok(toast) = app.toasts.rx.try_recv() {
+        app.toasts.push(toast);
+    }
+
+    app.toasts.active.retain(|(_, created)| created.elapsed() < TOAST_DURATION);
+
+    if app.toasts.active.is_empty() { return; }
+
+    let bg_col = egui::Color32::BLACK;
+    let painter = ui.painter();
+
+    // Newest toast at the bottom
+    for (i, (toast, created)) in app.toasts.active.iter().rev().enumerate() {
+        let txt_galley = painter.layout_no_wrap(toast.text().to_string(), egui::FontId::default(), toast.color());
+
+        let pos = egui::pos2(
+            (app.window_size.x - txt_galley.rect.width()) / 2.0,
+            app.window_size.y - TOAST_SPACING * (i + 1) as f32
+        );
+
+        let bg_rect = txt_galley.rect
+            .translate(pos.to_vec2())
+            .expand(5.0);
+
+        painter.rect_filled(bg_rect, egui::Rounding::same(3.0), bg_col);
+        painter.galley(pos, txt_galley, egui::Color32::WHITE);
+
+        // Repaint once the toast expires, so it disappears without input
+        ui.ctx().request_repaint_after(TOAST_DURATION.saturating_sub(created.elapsed()));
+    }
+}
@@ -1,6 +1,7 @@
 mod app;
 
 fn main() {
-    println!("Thermal Camera reading.");
+    // stderr, stdout is reserved for data of the stream subcommand
+    eprintln!("Thermal Camera reading.");
     app::run();
 }